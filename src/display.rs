@@ -0,0 +1,249 @@
+use std::fmt;
+
+use crate::executor::{MathType, TrigMode};
+use crate::matrix::Matrix;
+use crate::number::Number;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisplayMode {
+    Auto,
+    Fixed,
+    Sci,
+    Eng,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComplexDisplay {
+    Rect,
+    Polar,
+}
+
+/// How numbers get printed, taken from the environment's display mode and digit cap.
+#[derive(Debug, Clone, Copy)]
+pub struct NumberFormat {
+    pub mode: DisplayMode,
+    pub digits: u8,
+    pub complex: ComplexDisplay,
+    pub trig_mode: TrigMode, // unit for polar angles
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self { mode: DisplayMode::Auto, digits: 9, complex: ComplexDisplay::Rect, trig_mode: TrigMode::Deg }
+    }
+}
+
+// splits a number rounded to `significant` digits into its mantissa digits and decimal exponent
+fn rounded_parts(num: f64, significant: usize) -> (f64, i32) {
+    let sci = format!("{:.*e}", significant - 1, num);
+    let (mantissa, exponent) = sci.split_once('e').expect("scientific format always has an exponent");
+    (mantissa.parse().unwrap_or(num), exponent.parse().unwrap_or(0))
+}
+
+fn trim_zeros(text: String) -> String {
+    match text.contains('.') {
+        true => text.trim_end_matches('0').trim_end_matches('.').to_string(),
+        false => text,
+    }
+}
+
+// "-0.00" reads like a bug, so anything that rounds to zero is printed without a sign
+fn drop_negative_zero(text: String) -> String {
+    match text.strip_prefix('-') {
+        Some(rest) if rest.chars().all(|c| c == '0' || c == '.') => rest.to_string(),
+        _ => text,
+    }
+}
+
+fn format_auto(num: f64, significant: usize) -> String {
+    let (mantissa, exponent) = rounded_parts(num, significant);
+    if (-5..significant as i32).contains(&exponent) {
+        let decimals = (significant as i32 - 1 - exponent).max(0) as usize;
+        trim_zeros(format!("{:.*}", decimals, num))
+    } else {
+        format!("{}e{}", trim_zeros(format!("{:.*}", significant - 1, mantissa)), exponent)
+    }
+}
+
+fn format_eng(num: f64, significant: usize) -> String {
+    let (mantissa, exponent) = rounded_parts(num, significant);
+    let eng_exponent = exponent.div_euclid(3) * 3;
+    let shift = exponent - eng_exponent;
+    let decimals = (significant as i32 - 1 - shift).max(0) as usize;
+    format!("{:.*}e{}", decimals, mantissa * 10f64.powi(shift), eng_exponent)
+}
+
+fn format_complex(re: f64, im: f64, format: NumberFormat) -> String {
+    // a part this much smaller than the other is float noise, like the imaginary part of e^(i pi)
+    let magnitude = re.hypot(im);
+    let re = if re.abs() < magnitude * 1e-15 { 0.0 } else { re };
+    let im = if im.abs() < magnitude * 1e-15 { 0.0 } else { im };
+    if im == 0.0 {
+        return format_float(re, format);
+    }
+    match format.complex {
+        ComplexDisplay::Rect => {
+            let imaginary = format!("{}i", format_float(im.abs(), format));
+            match (re == 0.0, im < 0.0) {
+                (true, false) => imaginary,
+                (true, true) => format!("-{imaginary}"),
+                (false, negative) => format!("{} {} {}", format_float(re, format), if negative {'-'} else {'+'}, imaginary),
+            }
+        },
+        ComplexDisplay::Polar => match format.trig_mode {
+            TrigMode::Rad => format!("{}∠{}", format_float(magnitude, format), format_float(im.atan2(re), format)),
+            TrigMode::Deg => format!("{}∠{}°", format_float(magnitude, format), format_float(im.atan2(re).to_degrees(), format)),
+        },
+    }
+}
+
+pub fn format_number(num: Number, format: NumberFormat) -> String {
+    match num {
+        Number::Ratio(numerator, 1) => numerator.to_string(),
+        Number::Ratio(numerator, denominator) => format!("{numerator}/{denominator}"),
+        Number::Float(num) => format_float(num, format),
+        Number::Complex(re, im) => format_complex(re, im, format),
+    }
+}
+
+fn format_float(num: f64, format: NumberFormat) -> String {
+    if !num.is_finite() {
+        return num.to_string();
+    }
+    let significant = (format.digits as usize).max(1);
+    let text = match format.mode {
+        DisplayMode::Auto if num == 0.0 => "0".to_string(),
+        DisplayMode::Auto => format_auto(num, significant),
+        DisplayMode::Fixed => format!("{:.*}", format.digits as usize, num),
+        DisplayMode::Sci => format!("{:.*e}", significant - 1, num),
+        DisplayMode::Eng => format_eng(num, significant),
+    };
+    drop_negative_zero(text)
+}
+
+fn write_matrix<T: Copy>(f: &mut fmt::Formatter<'_>, matrix: &Matrix<T>, entry: impl Fn(&T) -> String) -> fmt::Result {
+    // vectors fit on one line, written the same way you would type them in
+    if matrix.is_vector() || matrix.entries().is_empty() {
+        let separator = if matrix.height() == 1 { ", " } else { "; " };
+        let entries: Vec<String> = matrix.entries().iter().map(entry).collect();
+        return write!(f, "[{}]", entries.join(separator));
+    }
+    writeln!(f, "[")?;
+    for row in matrix.rows() {
+        write!(f, "\t")?;
+        for value in row {
+            write!(f, "{}, ", entry(value))?;
+        }
+        writeln!(f)?;
+    }
+    writeln!(f, "]")
+}
+
+pub struct MathDisplay<'a> {
+    value: &'a MathType,
+    format: NumberFormat,
+}
+
+impl MathType {
+    pub fn display(&self, format: NumberFormat) -> MathDisplay<'_> {
+        MathDisplay { value: self, format }
+    }
+}
+
+impl fmt::Display for MathDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let number = |num: &Number| format_number(*num, self.format);
+        match self.value {
+            MathType::Number(num) => write!(f, "{}", number(num)),
+            MathType::Bool(value) => write!(f, "{value}"),
+            MathType::Matrix(matrix) => write_matrix(f, matrix, number),
+            MathType::Mask(mask) => write_matrix(f, mask, bool::to_string),
+            MathType::Quantity(num, unit) => write!(f, "{} {unit}", number(num)),
+            MathType::Tuple(parts) => {
+                for (name, part) in parts {
+                    let text = part.display(self.format).to_string();
+                    write!(f, "{name} = {text}")?;
+                    if !text.ends_with('\n') {
+                        writeln!(f)?;
+                    }
+                }
+                Ok(())
+            },
+            MathType::Function(function) => match function.params.as_slice() {
+                [param] => write!(f, "{param} -> {}", function.body),
+                params => write!(f, "({}) -> {}", params.join(", "), function.body),
+            },
+        }
+    }
+}
+
+impl fmt::Display for MathType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.display(NumberFormat::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(num: f64, mode: DisplayMode, digits: u8) -> String {
+        format_number(Number::from(num), NumberFormat { mode, digits, ..Default::default() })
+    }
+
+    #[test]
+    fn auto_rounds_to_significant_digits() {
+        assert_eq!(format(0.1 + 0.2, DisplayMode::Auto, 9), "0.3");
+        assert_eq!(format(10.0, DisplayMode::Auto, 9), "10");
+        assert_eq!(format(2.0 / 3.0, DisplayMode::Auto, 4), "0.6667");
+        assert_eq!(format(6.022e23, DisplayMode::Auto, 9), "6.022e23");
+        assert_eq!(format(1.6e-19, DisplayMode::Auto, 9), "1.6e-19");
+        assert_eq!(format(-0.0, DisplayMode::Auto, 9), "0");
+        assert_eq!(format(f64::INFINITY, DisplayMode::Auto, 9), "inf");
+    }
+
+    #[test]
+    fn fixed_sci_and_eng() {
+        assert_eq!(format(1.23456, DisplayMode::Fixed, 2), "1.23");
+        assert_eq!(format(-0.001, DisplayMode::Fixed, 2), "0.00");
+        assert_eq!(format(12345.0, DisplayMode::Sci, 3), "1.23e4");
+        assert_eq!(format(12345.0, DisplayMode::Eng, 3), "12.3e3");
+        assert_eq!(format(0.00047, DisplayMode::Eng, 2), "470e-6");
+        assert_eq!(format(999.96, DisplayMode::Eng, 4), "1.000e3");
+    }
+
+    #[test]
+    fn formats_every_cell() {
+        let format = NumberFormat { digits: 3, ..Default::default() };
+        let vector = MathType::Matrix(Matrix::row_vector(vec![Number::from(1.0 / 3.0), Number::from(2.0 / 3.0)]));
+        assert_eq!(vector.display(format).to_string(), "[0.333, 0.667]");
+        let column = MathType::Matrix(Matrix::column_vector(vec![Number::from(1), Number::from(2)]));
+        assert_eq!(column.display(format).to_string(), "[1; 2]");
+        let matrix = MathType::Matrix(Matrix::from_rows(&[
+            vec![Number::from(0.1 + 0.2), Number::from(2)],
+            vec![Number::from(1), Number::from(1.0 / 3.0)],
+        ]));
+        assert_eq!(matrix.display(format).to_string(), "[\n\t0.3, 2, \n\t1, 0.333, \n]\n");
+        assert_eq!(MathType::Bool(false).to_string(), "false");
+        assert_eq!(MathType::Mask(Matrix::row_vector(vec![true, false])).to_string(), "[true, false]");
+    }
+
+    #[test]
+    fn fractions_print_exactly() {
+        let format = NumberFormat::default();
+        assert_eq!(format_number(Number::ratio(1, 2), format), "1/2");
+        assert_eq!(format_number(Number::ratio(-6, 3), format), "-2");
+    }
+
+    #[test]
+    fn complex_rect_and_polar() {
+        let rect = NumberFormat::default();
+        assert_eq!(format_number(Number::complex(3.0, -4.0), rect), "3 - 4i");
+        assert_eq!(format_number(Number::complex(0.0, 1.0), rect), "1i");
+        assert_eq!(format_number(Number::complex(-1.0, 1.2e-16), rect), "-1");
+        let polar = NumberFormat { complex: ComplexDisplay::Polar, ..Default::default() };
+        assert_eq!(format_number(Number::complex(1.0, 1.0), polar), "1.41421356∠45°");
+        let polar_rad = NumberFormat { trig_mode: TrigMode::Rad, ..polar };
+        assert_eq!(format_number(Number::complex(0.0, -2.0), polar_rad), "2∠-1.57079633");
+    }
+}
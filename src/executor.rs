@@ -1,86 +1,134 @@
 use std::collections::HashMap;
 use std::error::Error;
 use std::f64::consts;
+use std::sync::Arc;
 use std::{fmt, iter};
 
+use crate::display::{ComplexDisplay, DisplayMode, NumberFormat};
+use crate::matrix::Matrix;
+use crate::number::Number;
 use crate::operations;
-use crate::tree_builder::Node;
+use crate::tokens::Span;
+use crate::tree_builder::{Expr, ExprKind};
+use crate::units::Unit;
+
+// a recursive function can nest any amount of expression inside each call, so the stack runs out after some number of
+// expressions rather than calls. the worst case is under 5KB each in a debug build, so this fits twice in the stack in main.rs
+const MAX_EVALUATION_DEPTH: usize = 6_000;
 
 #[derive(Debug)]
 pub enum ExecutionError {
     UnknownOperator(String),
-    UnknownExpression(String),
     UnknownIdentifier(String),
     InvalidOperation(String),
     InvalidVectorContents(String),
     MatrixUnequalRowLengths,
     WrongNumFunctionArgs(usize, usize),
     BadFunctionArgs(String),
+    IncompatibleUnits(String, String),
+    NotImplemented(String),
+    SingularMatrix,
+    TooMuchRecursion(usize),
+    Located(Box<ExecutionError>, Span),
+}
+
+impl ExecutionError {
+    /// Attaches the location of the node that failed, keeping the innermost location if one is already set.
+    pub fn at(self, span: Span) -> Self {
+        match self {
+            ExecutionError::Located(..) => self,
+            e => ExecutionError::Located(Box::new(e), span),
+        }
+    }
+    /// Drops the location, used when an error came from a different line such as a user function body.
+    pub fn unlocated(self) -> Self {
+        match self {
+            ExecutionError::Located(e, _) => *e,
+            e => e,
+        }
+    }
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ExecutionError::Located(_, span) => Some(*span),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for ExecutionError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ExecutionError::UnknownOperator(e) => write!(f, "operator '{e}' does not exist"),
-            ExecutionError::UnknownExpression(e) => write!(f, "could not parse expression: {e}"),
             ExecutionError::UnknownIdentifier(e) => write!(f, "unknown identifier: {e}"),
             ExecutionError::InvalidOperation(e) => write!(f, "invalid operation: {e}"),
             ExecutionError::InvalidVectorContents(e) => write!(f, "cannot contain '{e}' in vector"),
             ExecutionError::MatrixUnequalRowLengths => write!(f, "matrix row lengths are unequal"),
             ExecutionError::WrongNumFunctionArgs(a, b) => write!(f, "called function requiring {a} params with {b} args"),
             ExecutionError::BadFunctionArgs(e) => write!(f, "bad arguments for {}", e),
+            ExecutionError::IncompatibleUnits(a, b) => write!(f, "incompatible units: {a} and {b}"),
+            ExecutionError::NotImplemented(e) => write!(f, "{e} is not implemented yet"),
+            ExecutionError::SingularMatrix => write!(f, "matrix is singular"),
+            ExecutionError::TooMuchRecursion(0) => write!(f, "expression is too long to work out"),
+            ExecutionError::TooMuchRecursion(e) => write!(f, "recursion went too deep and stopped {e} function calls in, the limit on calls can be changed with `mode depth`"),
+            ExecutionError::Located(e, _) => write!(f, "{e}"),
         }
     }
 }
 
 impl Error for ExecutionError {}
 
-pub type Number = f64;
-
 #[derive(Debug, Clone)]
 pub enum MathType {
     Number(Number),
-    Vector(Vec<Number>),
-    Matrix(Vec<Vec<Number>>),
+    Bool(bool),
+    Matrix(Matrix), // vectors are matrices with a single row or column
+    Mask(Matrix<bool>), // from comparing a vector or matrix entry by entry, like `x > 2`
+    Quantity(Number, Unit),
+    Tuple(Vec<(&'static str, MathType)>), // several named results from one builtin, like the factors from lu
+    Function(Arc<Function>), // a lambda like `x -> x^2`, or a user function passed by name
 }
 
-impl fmt::Display for MathType {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            MathType::Number(number) => write!(f, "{}", number),
-            MathType::Vector(vector) => {
-                write!(f, "[")?;
-                if let Some(first) = vector.get(0) {
-                    write!(f, "{}", first)?
-                }
-                for number in vector.iter().skip(1) {
-                    write!(f, ", {}", number)?;
-                }
-                write!(f, "]")
-            },
-            MathType::Matrix(matrix) => {
-                let width = matrix.len();
-                let height = matrix.get(0).map(|r| r.len()).unwrap_or(0);
-                write!(f, "[\n")?;
-                let mut row = 0;
-                while row < height {
-                    write!(f, "\t")?;
-                    let mut col = 0;
-                    while col < width {
-                        write!(f, "{}, ", matrix[col][row])?;
-                        col += 1;
-                    }
-                    write!(f, "\n")?;
-                    row += 1;
-                }
-                write!(f, "]\n")
-            },
+/// A user function or lambda. It keeps the parameters that were around where it was written,
+/// so `def adder(n) x -> x + n` hands back a function that still knows `n` after adder returns.
+#[derive(Debug)]
+pub struct Function {
+    pub params: Vec<String>,
+    pub body: Arc<Expr>, // shared with the tree it came from, so making a lambda doesn't copy its body
+    pub captured: HashMap<String, MathType>,
+}
+
+impl From<Number> for MathType {
+    fn from(num: Number) -> Self {
+        MathType::Number(num)
+    }
+}
+
+impl From<bool> for MathType {
+    fn from(value: bool) -> Self {
+        MathType::Bool(value)
+    }
+}
+
+// a 1x1 matrix is just a number, like `[5]` or a row vector times a column vector
+impl From<Matrix> for MathType {
+    fn from(matrix: Matrix) -> Self {
+        match (matrix.height(), matrix.width()) {
+            (1, 1) => MathType::Number(matrix[(0, 0)]),
+            _ => MathType::Matrix(matrix),
         }
     }
 }
 
+impl From<Matrix<bool>> for MathType {
+    fn from(mask: Matrix<bool>) -> Self {
+        match (mask.height(), mask.width()) {
+            (1, 1) => MathType::Bool(mask[(0, 0)]),
+            _ => MathType::Mask(mask),
+        }
+    }
+}
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TrigMode {
     Rad,
     Deg,
@@ -89,163 +137,262 @@ pub enum TrigMode {
 // should probably have a constructor that reads these from config
 #[derive(Clone)]
 pub struct Environment {
-    pub user_vars: HashMap<String, MathType>,
-    pub user_functions: HashMap<String, (Node, Node)>, // name to (params, body)
+    pub user_vars: HashMap<String, MathType>, // globals, what you see at the prompt
+    pub user_functions: HashMap<String, Arc<Function>>, // shared so a call doesn't copy the body
+    // one frame of parameters for each user function that's running, a function only sees its own frame and the globals
+    pub call_frames: Vec<HashMap<String, MathType>>,
     pub trig_mode: TrigMode,
     pub digit_cap: u8,
+    pub display_mode: DisplayMode,
+    pub implicit_multiplication: bool,
+    pub exact_mode: bool,
+    pub broadcasting: bool,
+    pub max_call_depth: usize,
+    pub evaluation_depth: usize, // how many expressions are being evaluated inside each other, across every running call
+    pub complex_display: ComplexDisplay,
+}
+
+impl Environment {
+    pub fn number_format(&self) -> NumberFormat {
+        NumberFormat {
+            mode: self.display_mode,
+            digits: self.digit_cap,
+            complex: self.complex_display,
+            trig_mode: self.trig_mode,
+        }
+    }
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.user_vars.contains_key(name) || self.user_functions.contains_key(name)
+    }
+    pub fn get_var(&self, name: &str) -> Option<&MathType> {
+        match self.call_frames.last().and_then(|frame| frame.get(name)) {
+            Some(value) => Some(value),
+            None => self.user_vars.get(name),
+        }
+    }
+    /// Assigns in the innermost function call, so a function body can never change a global.
+    pub fn set_var(&mut self, name: String, value: MathType) {
+        match self.call_frames.last_mut() {
+            Some(frame) => frame.insert(name, value),
+            None => self.user_vars.insert(name, value),
+        };
+    }
 }
 
 impl Default for Environment {
     fn default() -> Self {
         Self {
             user_vars: HashMap::from([
-                ("pi".to_string(), MathType::Number(consts::PI as Number)),
-                ("PI".to_string(), MathType::Number(consts::PI as Number)),
-                ("e".to_string(), MathType::Number(consts::E as Number)),
-                ("E".to_string(), MathType::Number(consts::E as Number)),
-                ("ans".to_string(), MathType::Number(0 as Number)),
+                ("pi".to_string(), MathType::Number(Number::from(consts::PI))),
+                ("PI".to_string(), MathType::Number(Number::from(consts::PI))),
+                ("e".to_string(), MathType::Number(Number::from(consts::E))),
+                ("E".to_string(), MathType::Number(Number::from(consts::E))),
+                ("i".to_string(), MathType::Number(Number::complex(0.0, 1.0))),
+                ("true".to_string(), MathType::Bool(true)),
+                ("false".to_string(), MathType::Bool(false)),
+                ("ans".to_string(), MathType::Number(Number::zero())),
             ]),
             user_functions: HashMap::new(),
+            call_frames: Vec::new(),
             trig_mode: TrigMode::Deg,
             digit_cap: 9,
+            display_mode: DisplayMode::Auto,
+            implicit_multiplication: true,
+            exact_mode: false,
+            broadcasting: false,
+            max_call_depth: 1000,
+            evaluation_depth: 0,
+            complex_display: ComplexDisplay::Rect,
         }
     }
 }
 
-fn handle_assignment(lhs: &Node, rhs: &Node, operator: &str, environment: &mut Environment) -> Result<MathType, ExecutionError> {
-    let (identifier, value) = match operator {
-        "=" => (lhs, rhs),
-        "=>" => (rhs, lhs),
-        _ => panic!("should be an assignment operator"),
-    };
-    let identifier = identifier.as_identifier().ok_or(ExecutionError::InvalidOperation("invalid variable name".to_string()))?;
-    let value = execute_expression_tree(value, environment)?;
-    environment.user_vars.insert(identifier.to_string(), value.clone());
-    Ok(value)
-}
-
-fn handle_matrix_creation(nodes: &Vec<Node>, environment: &mut Environment) -> Result<MathType, ExecutionError> {
-    let mut vectors: Vec<Vec<Number>> = Vec::new();
-    let mut col = 1;
+fn handle_matrix_creation(rows: &[Vec<Expr>], environment: &mut Environment) -> Result<MathType, ExecutionError> {
+    let mut values: Vec<Vec<Number>> = Vec::new();
 
-    for node in nodes.iter().skip(1) {
-        if node.is_str(",") || node.is_str("]") {
-            continue;
-        } else if node.is_str(";") {
-            col = 0;
-        } else {
-            let element = execute_expression_tree(node, environment)?;
+    for row in rows {
+        if row.len() != rows[0].len() {
+            return Err(ExecutionError::MatrixUnequalRowLengths);
+        }
+        let mut numbers = Vec::new();
+        for expr in row {
+            let element = execute_expression_tree(expr, environment)?;
             match element {
-                MathType::Number(num) => match vectors.get_mut(col) {
-                    Some(vector) => vector.push(num),
-                    None => vectors.push(vec![num]),
-                },
-                _ => return Err(ExecutionError::InvalidVectorContents(element.to_string())),
+                MathType::Number(num) => numbers.push(num),
+                _ => return Err(ExecutionError::InvalidVectorContents(element.to_string()).at(expr.span)),
             }
-            col += 1;
         }
+        values.push(numbers);
     }
 
-    let height = vectors.get(0).map(|v| v.len()).unwrap_or(0);
-    if vectors.iter().any(|vector| vector.len() != height) {
-        return Err(ExecutionError::MatrixUnequalRowLengths);
+    Ok(MathType::from(Matrix::from_rows(&values)))
+}
+
+/// Runs a user function or lambda in a new frame holding its captured values and arguments.
+pub fn call_function(function: &Function, args: Vec<MathType>, environment: &mut Environment) -> Result<MathType, ExecutionError> {
+    if function.params.len() != args.len() {
+        return Err(ExecutionError::WrongNumFunctionArgs(function.params.len(), args.len()));
+    }
+    if environment.call_frames.len() >= environment.max_call_depth {
+        return Err(ExecutionError::TooMuchRecursion(environment.max_call_depth));
     }
 
-    if vectors.len() == 0 {
-        return Ok(MathType::Vector(Vec::new()));
-    } else if vectors.len() == 1 {
-        return Ok(MathType::Vector(vectors.remove(0)));
-    } else {
-        return Ok(MathType::Matrix(vectors));
+    let mut frame = function.captured.clone();
+    frame.extend(iter::zip(function.params.iter().cloned(), args));
+    environment.call_frames.push(frame);
+    let result = execute_expression_tree(&function.body, environment);
+    environment.call_frames.pop();
+    result
+}
+
+/// Evaluates an expression, failing once too many are nested inside each other rather than running out of stack.
+pub fn execute_expression_tree(expr: &Expr, environment: &mut Environment) -> Result<MathType, ExecutionError> {
+    if environment.evaluation_depth >= MAX_EVALUATION_DEPTH {
+        return Err(ExecutionError::TooMuchRecursion(environment.call_frames.len()));
     }
+    environment.evaluation_depth += 1;
+    let result = evaluate(expr, environment);
+    environment.evaluation_depth -= 1;
+    result
 }
 
-fn process_function_args(args: &Node, environment: &mut Environment) -> Result<Vec<MathType>, ExecutionError> {
-    match args {
-        Node::Tkn(_) => Ok(vec![execute_expression_tree(args, environment)?]),
-        Node::Exp(subnodes) => subnodes
-            .split(|e| e.is_str(","))
-            .map(|nodes| execute_expression_tree(&Node::Exp(nodes.to_vec()), environment))
-            .collect()
+// every arm hands off to its own function, since this frame is on the stack once for each level of nesting
+// and would otherwise be as big as all the arms put together
+fn evaluate(expr: &Expr, environment: &mut Environment) -> Result<MathType, ExecutionError> {
+    match &expr.kind {
+        ExprKind::Number(number) => match environment.exact_mode {
+            true => Ok(MathType::Number(*number)),
+            false => Ok(MathType::Number(number.to_float())),
+        },
+        ExprKind::Ident(name) => handle_identifier(name, false, environment).map_err(|e| e.at(expr.span)),
+        ExprKind::Call { name, args, parens } => handle_call(name, args, *parens, environment).map_err(|e| e.at(expr.span)),
+        ExprKind::Unary { op, operand } | ExprKind::Postfix { op, operand } => handle_unary(op, operand, environment).map_err(|e| e.at(expr.span)),
+        ExprKind::Binary { op, lhs, rhs } => handle_binary(op, lhs, rhs, environment).map_err(|e| e.at(expr.span)),
+        ExprKind::Lambda { params, body } => Ok(handle_lambda(params, body, environment)),
+        ExprKind::Matrix { rows } => handle_matrix_creation(rows, environment).map_err(|e| e.at(expr.span)),
+        ExprKind::Conditional { condition, then, otherwise } => handle_conditional(condition, then, otherwise, environment),
+        ExprKind::Destructure { names, value } => handle_destructure(names, value, environment).map_err(|e| e.at(expr.span)),
+        ExprKind::Assign { name, value } => handle_assignment(name, value, environment),
     }
 }
 
-fn handle_user_function_call(body: &Node, params: &Node, args: Vec<MathType>, mut environment: Environment) -> Result<MathType, ExecutionError> {
-    let params: Vec<String> = match params {
-        Node::Tkn(token) => vec![token.clone()],
-        Node::Exp(subnodes) => subnodes.iter()
-            .filter(|node| !node.is_str(","))
-            .filter_map(|node| node.as_identifier())
-            .map(|node| node.clone())
-            .collect()
+// a unit symbol is only a unit when `units` says it's part of a unit expression, otherwise it's just an undefined variable
+fn handle_identifier(name: &str, units: bool, environment: &Environment) -> Result<MathType, ExecutionError> {
+    let unit = units.then(|| Unit::parse(name)).flatten();
+    match (environment.get_var(name), environment.user_functions.get(name), unit) {
+        (Some(value), _, _) => Ok(value.clone()),
+        (None, Some(function), _) => Ok(MathType::Function(function.clone())),
+        (None, None, Some(unit)) => Ok(MathType::Quantity(Number::one(), unit)),
+        (None, None, None) => Err(ExecutionError::UnknownIdentifier(name.to_string())),
+    }
+}
+
+// the operands of `*`, `/` and `^` and the target of `to` can be units, so `5 m`, `m/s` and `to km/h` all work
+fn evaluate_operand(expr: &Expr, units: bool, environment: &mut Environment) -> Result<MathType, ExecutionError> {
+    match &expr.kind {
+        ExprKind::Ident(name) => handle_identifier(name, units, environment).map_err(|e| e.at(expr.span)),
+        _ => execute_expression_tree(expr, environment),
+    }
+}
+
+fn handle_call(name: &str, args: &[Expr], parens: bool, environment: &mut Environment) -> Result<MathType, ExecutionError> {
+    // a parameter holding a function comes first, so `def twice(f, x) f(f(x))` calls whatever was passed in
+    let function = match environment.get_var(name) {
+        Some(MathType::Function(function)) => Some(function.clone()),
+        _ => environment.user_functions.get(name).cloned(),
+    };
+    // a unit written before something multiplies it, so `kg m` is `kg*m`, but with brackets
+    // like `h(2)` it's a call to a function that isn't there
+    let unit = match (parens, &function, Unit::parse(name)) {
+        (false, None, Some(unit)) => Some(unit),
+        _ => None,
     };
+    let function_args = args.iter()
+        .map(|arg| evaluate_operand(arg, unit.is_some(), environment))
+        .collect::<Result<Vec<MathType>, ExecutionError>>()?;
+    if let Some(function) = function {
+        return call_function(&function, function_args, environment).map_err(ExecutionError::unlocated);
+    }
+    let unit_product = unit.and_then(|unit| function_args.first().cloned().map(|arg| (unit, arg)));
+    // a variable doesn't hide a builtin with the same name, but if there isn't one it's clearer to say what the variable is
+    operations::execute_builtin_function(name, function_args, environment).or_else(|e| match (e, unit_product, environment.get_var(name)) {
+        (ExecutionError::UnknownIdentifier(_), Some((unit, arg)), _) => MathType::Quantity(Number::one(), unit).operate("*", arg),
+        (ExecutionError::UnknownIdentifier(_), None, Some(value)) => {
+            Err(ExecutionError::InvalidOperation(format!("{name} is a {}, not a function", value.type_name())))
+        },
+        (e, ..) => Err(e),
+    })
+}
 
-    if params.len() != args.len() {
-        return Err(ExecutionError::WrongNumFunctionArgs(params.len(), args.len()));
+// prefix and postfix operators, which only have the one operand
+fn handle_unary(op: &str, operand: &Expr, environment: &mut Environment) -> Result<MathType, ExecutionError> {
+    let operand = execute_expression_tree(operand, environment)?;
+    match op {
+        "-" => operand.operate("*", MathType::Number(Number::from(-1))),
+        "!" => operand.not(),
+        "'" => operand.transpose(),
+        _ => Err(ExecutionError::UnknownOperator(op.to_string())),
     }
+}
 
-    for (param, arg) in iter::zip(params, args) {
-        environment.user_vars.insert(param, arg);
+fn handle_binary(op: &str, lhs: &Expr, rhs: &Expr, environment: &mut Environment) -> Result<MathType, ExecutionError> {
+    let (lhs_units, rhs_units) = match op {
+        "*" | "/" | "\\" => (true, true),
+        "^" => (true, false),
+        "to" => (false, true),
+        _ => (false, false),
+    };
+    let lhs = evaluate_operand(lhs, lhs_units, environment)?;
+    // the right side doesn't run when the left already decides it, so `n == 0 || f(n - 1)` can stop
+    if let ("&&", MathType::Bool(false)) | ("||", MathType::Bool(true)) = (op, &lhs) {
+        return Ok(lhs);
     }
+    let rhs = evaluate_operand(rhs, rhs_units, environment)?;
+    let (lhs, rhs) = match environment.broadcasting {
+        true => operations::broadcast(lhs, op, rhs)?,
+        false => (lhs, rhs),
+    };
+    lhs.operate(op, rhs)
+}
 
-    execute_expression_tree(body, &mut environment)
+fn handle_lambda(params: &[String], body: &Arc<Expr>, environment: &Environment) -> MathType {
+    MathType::Function(Arc::new(Function {
+        params: params.to_vec(),
+        body: body.clone(),
+        captured: environment.call_frames.last().cloned().unwrap_or_default(),
+    }))
 }
 
-pub fn execute_expression_tree(root_node: &Node, environment: &mut Environment) -> Result<MathType, ExecutionError> {
-    match root_node {
-        Node::Tkn(token) => {
-            if let Ok(number) = token.parse::<Number>() {
-                Ok(MathType::Number(number))
-            } else if let Some(value) = environment.user_vars.get(token) {
-                Ok(value.clone())
-            } else {
-                Err(ExecutionError::UnknownIdentifier(token.clone()))
-            }
+// only the branch that gets picked runs, so a recursive function can stop at its base case
+fn handle_conditional(condition: &Expr, then: &Expr, otherwise: &Expr, environment: &mut Environment) -> Result<MathType, ExecutionError> {
+    match execute_expression_tree(condition, environment)? {
+        MathType::Bool(true) => execute_expression_tree(then, environment),
+        MathType::Bool(false) => execute_expression_tree(otherwise, environment),
+        other => {
+            let message = format!("the condition after 'if' has to be true or false, not a {}", other.type_name());
+            Err(ExecutionError::InvalidOperation(message).at(condition.span))
         },
-        Node::Exp(subnodes) => {
-            if let Some(first) = subnodes.get(0) {
-                if first.is_str("[") {
-                    return handle_matrix_creation(subnodes, environment);
-                }
-            }
+    }
+}
 
-            match subnodes.len() {
-                0 => Ok(MathType::Number(0.0)),
-                1 => execute_expression_tree(&subnodes[0], environment),
-                2 => {
-                    let left_node = &subnodes[0];
-                    let right_node = &subnodes[1];
-
-                    if left_node.is_unary_operator() { // only expecting '-' currently
-                        execute_expression_tree(right_node, environment)?.operate("*", MathType::Number(-1.0))
-
-                    } else if let Node::Tkn(token) = left_node { // expecting function call
-                        let mut function_env = environment.clone();
-                        let function_args = process_function_args(right_node, &mut function_env)?;
-                        match environment.user_functions.get(token) {
-                            Some((params, body)) => handle_user_function_call(body, params, function_args, function_env),
-                            None => operations::execute_builtin_function(token, function_args)
-                        }
-                    } else {
-                        Err(ExecutionError::UnknownExpression(format!("left: {}; right: {};", left_node.flat_string(), right_node.flat_string())))
-                    }
-                },
-                3 => {
-                    let operator = &subnodes[1];
-                    if let Some(assignment_operator) = operator.as_assignment_operator() {
-                        handle_assignment(&subnodes[0], &subnodes[2], assignment_operator, environment)
-                    } else if let Node::Tkn(op) = operator {
-                        let lhs = execute_expression_tree(&subnodes[0], environment)?;
-                        let rhs = execute_expression_tree(&subnodes[2], environment)?;
-                        lhs.operate(&op, rhs)
-                    } else {
-                        Err(ExecutionError::UnknownExpression(format!("lhs: {}; op: {}; rhs: {}", subnodes[0].flat_string(), operator.flat_string(), subnodes[1].flat_string())))
-                    }
-                },
-                _ => {
-                    Err(ExecutionError::UnknownExpression(String::from_iter(subnodes.iter().map(|node| node.flat_string()))))
-                },
-            }
-        }
+fn handle_destructure(names: &[String], value: &Expr, environment: &mut Environment) -> Result<MathType, ExecutionError> {
+    let value = execute_expression_tree(value, environment)?;
+    let parts: Vec<MathType> = match &value {
+        MathType::Tuple(parts) => parts.iter().map(|(_, part)| part.clone()).collect(),
+        other => vec![other.clone()],
+    };
+    if names.len() > parts.len() {
+        let message = format!("can't unpack {} values into {} names", parts.len(), names.len());
+        return Err(ExecutionError::InvalidOperation(message));
     }
+    for (name, part) in iter::zip(names, parts) {
+        environment.set_var(name.clone(), part);
+    }
+    Ok(value)
+}
+
+fn handle_assignment(name: &str, value: &Expr, environment: &mut Environment) -> Result<MathType, ExecutionError> {
+    let value = execute_expression_tree(value, environment)?;
+    environment.set_var(name.to_string(), value.clone());
+    Ok(value)
 }
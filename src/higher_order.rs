@@ -0,0 +1,120 @@
+use crate::executor::{self, Environment, ExecutionError, Function, MathType};
+use crate::matrix::Matrix;
+use crate::number::Number;
+
+// numbers and bools count as a list of one, masks hand over their entries as true and false
+fn entries(fname: &str, value: &MathType) -> Result<Vec<MathType>, ExecutionError> {
+    match value {
+        MathType::Number(_) | MathType::Bool(_) => Ok(vec![value.clone()]),
+        MathType::Matrix(matrix) => Ok(matrix.entries().iter().copied().map(MathType::Number).collect()),
+        MathType::Mask(mask) => Ok(mask.entries().iter().copied().map(MathType::Bool).collect()),
+        _ => Err(ExecutionError::BadFunctionArgs(format!("{fname}, expected a vector or matrix, not a {}", value.type_name()))),
+    }
+}
+
+fn shape(value: &MathType) -> (usize, usize) {
+    match value {
+        MathType::Matrix(matrix) => (matrix.height(), matrix.width()),
+        MathType::Mask(mask) => (mask.height(), mask.width()),
+        _ => (1, 1),
+    }
+}
+
+// puts the results back into a height by width matrix, or a mask when they all came back true or false
+fn collect(fname: &str, height: usize, width: usize, results: &[MathType]) -> Result<MathType, ExecutionError> {
+    let numbers: Option<Vec<Number>> = results.iter().map(|result| match result {
+        MathType::Number(num) => Some(*num),
+        _ => None,
+    }).collect();
+    let bools: Option<Vec<bool>> = results.iter().map(|result| match result {
+        MathType::Bool(value) => Some(*value),
+        _ => None,
+    }).collect();
+    match (numbers, bools) {
+        (Some(numbers), _) => Ok(MathType::from(Matrix::from_fn(height, width, |row, col| numbers[row * width + col]))),
+        (None, Some(bools)) => Ok(MathType::from(Matrix::from_fn(height, width, |row, col| bools[row * width + col]))),
+        (None, None) => {
+            let odd_one = results.iter().find(|result| !matches!(result, MathType::Number(_))).expect("something wasn't a number");
+            Err(ExecutionError::InvalidOperation(format!("{fname} needs the function to give back a number or true or false each time, not a {}", odd_one.type_name())))
+        },
+    }
+}
+
+// the function body was written on another line, so its errors point at the call to map, filter and so on instead
+fn call_one(function: &Function, args: Vec<MathType>, environment: &mut Environment) -> Result<MathType, ExecutionError> {
+    executor::call_function(function, args, environment).map_err(ExecutionError::unlocated)
+}
+
+fn call_each(function: &Function, values: Vec<MathType>, environment: &mut Environment) -> Result<Vec<MathType>, ExecutionError> {
+    values.into_iter().map(|value| call_one(function, vec![value], environment)).collect()
+}
+
+fn map(function: &Function, value: &MathType, environment: &mut Environment) -> Result<MathType, ExecutionError> {
+    let (height, width) = shape(value);
+    let results = call_each(function, entries("map", value)?, environment)?;
+    collect("map", height, width, &results)
+}
+
+// keeps the entries the function says true to, a row stays a row and anything else gives a column
+fn filter(function: &Function, value: &MathType, environment: &mut Environment) -> Result<MathType, ExecutionError> {
+    let (height, width) = shape(value);
+    if height != 1 && width != 1 && height * width != 0 {
+        return Err(ExecutionError::BadFunctionArgs("filter, expected a vector".to_string()));
+    }
+    let values = entries("filter", value)?;
+    let keep = call_each(function, values.clone(), environment)?;
+    let mut kept = Vec::new();
+    for (value, keep) in values.into_iter().zip(keep) {
+        match keep {
+            MathType::Bool(true) => kept.push(value),
+            MathType::Bool(false) => {},
+            other => return Err(ExecutionError::InvalidOperation(format!("filter needs the function to give back true or false, not a {}", other.type_name()))),
+        }
+    }
+    let (height, width) = match (kept.len(), height == 1 && width != 1) {
+        (0, _) => (0, 0),
+        (count, true) => (1, count),
+        (count, false) => (count, 1),
+    };
+    collect("filter", height, width, &kept)
+}
+
+// `reduce((total, x) -> total + x, v, 0)` goes through the entries in order, starting from init
+fn reduce(function: &Function, value: &MathType, init: &MathType, environment: &mut Environment) -> Result<MathType, ExecutionError> {
+    entries("reduce", value)?
+        .into_iter()
+        .try_fold(init.clone(), |total, entry| call_one(function, vec![total, entry], environment))
+}
+
+// the function gets a whole vector at once, or each column of a matrix like the statistics builtins,
+// and the results go side by side in a row, or make up the columns of a matrix if they're columns themselves
+fn apply(function: &Function, value: &MathType, environment: &mut Environment) -> Result<MathType, ExecutionError> {
+    let columns: Vec<MathType> = match value {
+        MathType::Matrix(matrix) if !matrix.is_vector() && !matrix.entries().is_empty() => {
+            matrix.columns().map(|col| MathType::Matrix(Matrix::column_vector(col))).collect()
+        },
+        MathType::Mask(mask) if !mask.is_vector() => mask.columns().map(|col| MathType::Mask(Matrix::column_vector(col))).collect(),
+        _ => return call_one(function, vec![value.clone()], environment),
+    };
+    let results = call_each(function, columns, environment)?;
+    let column_results: Option<Vec<Vec<Number>>> = results.iter().map(|result| match result {
+        MathType::Matrix(matrix) if matrix.width() == 1 => Some(matrix.entries().to_vec()),
+        _ => None,
+    }).collect();
+    match column_results {
+        Some(columns) if columns.iter().all(|col| col.len() == columns[0].len()) => Ok(MathType::Matrix(Matrix::from_columns(&columns))),
+        _ => collect("apply", 1, results.len(), &results),
+    }
+}
+
+/// Builtins that take a function as their first argument, which can be a lambda or the name of a user function.
+pub fn call(fname: &str, args: Vec<MathType>, environment: &mut Environment) -> Result<MathType, ExecutionError> {
+    match (fname, args.as_slice()) {
+        ("map", [MathType::Function(function), value]) => map(function, value, environment),
+        ("filter", [MathType::Function(function), value]) => filter(function, value, environment),
+        ("reduce", [MathType::Function(function), value, init]) => reduce(function, value, init, environment),
+        ("apply", [MathType::Function(function), value]) => apply(function, value, environment),
+        ("reduce", _) => Err(ExecutionError::BadFunctionArgs("reduce, expected a function, a vector and a starting value".to_string())),
+        _ => Err(ExecutionError::BadFunctionArgs(format!("{fname}, expected a function and a vector or matrix"))),
+    }
+}
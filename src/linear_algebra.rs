@@ -0,0 +1,679 @@
+use std::cmp::Ordering;
+use std::iter;
+
+use crate::executor::{ExecutionError, MathType};
+use crate::matrix::Matrix;
+use crate::number::Number;
+
+/// Anything that can be treated as a matrix, a number is 1x1.
+pub fn as_matrix(value: &MathType) -> Option<Matrix> {
+    match value {
+        MathType::Number(num) => Some(Matrix::column_vector(vec![*num])),
+        MathType::Matrix(matrix) => Some(matrix.clone()),
+        MathType::Bool(_) | MathType::Mask(_) | MathType::Quantity(..) | MathType::Tuple(_) | MathType::Function(_) => None,
+    }
+}
+
+// elimination swaps and scales whole rows, so the algorithms here work on a list of rows
+fn as_rows(value: &MathType) -> Option<Vec<Vec<Number>>> {
+    as_matrix(value).map(|matrix| matrix.to_rows())
+}
+
+fn magnitude(num: Number) -> f64 {
+    num.abs().to_f64()
+}
+
+// anything within float error of zero counts as zero, the same tolerance Matlab uses for rref
+fn tolerance(rows: &[Vec<Number>]) -> f64 {
+    let size = rows.len().max(rows.first().map(|row| row.len()).unwrap_or(0));
+    let norm = rows.iter()
+        .map(|row| row.iter().map(|num| magnitude(*num)).sum::<f64>())
+        .fold(0.0, f64::max);
+    f64::EPSILON * size as f64 * norm
+}
+
+// exact fractions are only zero when they really are zero
+fn is_negligible(num: Number, tolerance: f64) -> bool {
+    match num {
+        Number::Ratio(numerator, _) => numerator == 0,
+        _ => magnitude(num) <= tolerance,
+    }
+}
+
+// partial pivoting: the row at or below `start` with the biggest entry in `col`
+fn pivot_row(rows: &[Vec<Number>], start: usize, col: usize) -> usize {
+    (start..rows.len())
+        .max_by(|a, b| magnitude(rows[*a][col]).total_cmp(&magnitude(rows[*b][col])))
+        .unwrap_or(start)
+}
+
+// subtracts multiples of the pivot row so `col` is zero in every other row (or just the ones below)
+fn eliminate(rows: &mut [Vec<Number>], pivot: usize, col: usize, below_only: bool) {
+    for row in 0..rows.len() {
+        if row == pivot || (below_only && row < pivot) {
+            continue;
+        }
+        let factor = rows[row][col] / rows[pivot][col];
+        if factor.is_zero() {
+            continue;
+        }
+        for i in col..rows[row].len() {
+            rows[row][i] = rows[row][i] - factor * rows[pivot][i];
+        }
+    }
+}
+
+fn square_rows(value: &MathType, fname: &str) -> Result<Vec<Vec<Number>>, ExecutionError> {
+    let rows = as_rows(value);
+    match rows {
+        Some(rows) if rows.iter().all(|row| row.len() == rows.len()) => Ok(rows),
+        _ => Err(ExecutionError::BadFunctionArgs(format!("{fname}, expected a square matrix"))),
+    }
+}
+
+pub fn cross(lhs: &[Number], rhs: &[Number]) -> Vec<Number> {
+    vec![
+        lhs[1] * rhs[2] - lhs[2] * rhs[1],
+        lhs[2] * rhs[0] - lhs[0] * rhs[2],
+        lhs[0] * rhs[1] - lhs[1] * rhs[0],
+    ]
+}
+
+pub fn det(value: &MathType) -> Result<Number, ExecutionError> {
+    let mut rows = square_rows(value, "det")?;
+    let tolerance = tolerance(&rows);
+    let mut determinant = Number::one();
+    for col in 0..rows.len() {
+        let pivot = pivot_row(&rows, col, col);
+        if is_negligible(rows[pivot][col], tolerance) {
+            return Ok(Number::zero());
+        }
+        if pivot != col {
+            rows.swap(pivot, col);
+            determinant = -determinant;
+        }
+        determinant = determinant * rows[col][col];
+        eliminate(&mut rows, col, col, true);
+    }
+    Ok(determinant)
+}
+
+pub fn inv(value: &MathType) -> Result<MathType, ExecutionError> {
+    let rows = square_rows(value, "inv")?;
+    let size = rows.len();
+    let tolerance = tolerance(&rows);
+    // Gauss-Jordan on [A | I], which leaves [I | A^-1]
+    let mut augmented: Vec<Vec<Number>> = rows.into_iter().enumerate().map(|(i, mut row)| {
+        row.extend((0..size).map(|j| if i == j { Number::one() } else { Number::zero() }));
+        row
+    }).collect();
+    for col in 0..size {
+        let pivot = pivot_row(&augmented, col, col);
+        if is_negligible(augmented[pivot][col], tolerance) {
+            return Err(ExecutionError::SingularMatrix);
+        }
+        augmented.swap(pivot, col);
+        let scale = augmented[col][col];
+        for num in augmented[col].iter_mut() {
+            *num = *num / scale;
+        }
+        eliminate(&mut augmented, col, col, false);
+    }
+    let inverse: Vec<Vec<Number>> = augmented.into_iter().map(|row| row[size..].to_vec()).collect();
+    Ok(MathType::from(Matrix::from_rows(&inverse)))
+}
+
+/// `A ^ n` for a whole number n, where a negative power is a power of the inverse.
+pub fn power(matrix: &Matrix, exponent: Number) -> Result<MathType, ExecutionError> {
+    let Some(exponent) = exponent.as_integer() else {
+        return Err(ExecutionError::InvalidOperation("matrices can only be raised to whole number powers, use .^ for each entry".to_string()));
+    };
+    if matrix.height() != matrix.width() {
+        return Err(ExecutionError::InvalidOperation(format!("can't raise a {}x{} matrix to a power, it has to be square", matrix.height(), matrix.width())));
+    }
+    let base = match exponent < 0 {
+        true => as_matrix(&inv(&MathType::Matrix(matrix.clone()))?).expect("the inverse is always a matrix or number"),
+        false => matrix.clone(),
+    };
+    Ok(MathType::from(base.power(exponent.unsigned_abs())))
+}
+
+pub fn rref(value: &MathType) -> Result<MathType, ExecutionError> {
+    let mut rows = match as_rows(value) {
+        Some(rows) => rows,
+        None => return Err(ExecutionError::BadFunctionArgs("rref".to_string())),
+    };
+    let tolerance = tolerance(&rows);
+    let width = rows.first().map(|row| row.len()).unwrap_or(0);
+    let mut pivot_count = 0;
+    for col in 0..width {
+        if pivot_count == rows.len() {
+            break;
+        }
+        let pivot = pivot_row(&rows, pivot_count, col);
+        if is_negligible(rows[pivot][col], tolerance) {
+            // nothing usable in this column, clean up the float noise so it prints as 0
+            for row in rows.iter_mut().skip(pivot_count) {
+                row[col] = Number::zero();
+            }
+            continue;
+        }
+        rows.swap(pivot, pivot_count);
+        let scale = rows[pivot_count][col];
+        for num in rows[pivot_count].iter_mut() {
+            *num = *num / scale;
+        }
+        eliminate(&mut rows, pivot_count, col, false);
+        pivot_count += 1;
+    }
+    for num in rows.iter_mut().flatten() {
+        if is_negligible(*num, tolerance) {
+            *num = Number::zero();
+        }
+    }
+    Ok(MathType::from(Matrix::from_rows(&rows)))
+}
+
+/// Solves `rows * x = rhs` by elimination with partial pivoting, works on exact, float and complex entries.
+pub fn solve(rows: &[Vec<Number>], rhs: &[Number]) -> Result<Vec<Number>, ExecutionError> {
+    let size = rows.len();
+    let tolerance = tolerance(rows);
+    let mut augmented: Vec<Vec<Number>> = iter::zip(rows, rhs).map(|(row, b)| {
+        let mut row = row.clone();
+        row.push(*b);
+        row
+    }).collect();
+    for col in 0..size {
+        let pivot = pivot_row(&augmented, col, col);
+        if is_negligible(augmented[pivot][col], tolerance) {
+            return Err(ExecutionError::SingularMatrix);
+        }
+        augmented.swap(pivot, col);
+        eliminate(&mut augmented, col, col, true);
+    }
+    // back substitution
+    let mut solution = vec![Number::zero(); size];
+    for row in (0..size).rev() {
+        let known: Number = (row + 1..size).map(|col| augmented[row][col] * solution[col]).sum();
+        solution[row] = (augmented[row][size] - known) / augmented[row][row];
+    }
+    Ok(solution)
+}
+
+fn float_rows(value: &MathType, fname: &str) -> Result<Vec<Vec<f64>>, ExecutionError> {
+    let rows = as_rows(value);
+    match rows {
+        Some(rows) if rows.iter().flatten().all(|num| !num.is_complex()) => {
+            Ok(rows.iter().map(|row| row.iter().map(|num| num.to_f64()).collect()).collect())
+        },
+        _ => Err(ExecutionError::BadFunctionArgs(format!("{fname}, expected a real matrix"))),
+    }
+}
+
+fn from_float_rows(rows: &[Vec<f64>]) -> MathType {
+    let rows: Vec<Vec<Number>> = rows.iter().map(|row| row.iter().map(|num| Number::from(*num)).collect()).collect();
+    MathType::Matrix(Matrix::from_rows(&rows))
+}
+
+fn is_symmetric(rows: &[Vec<f64>]) -> bool {
+    let tolerance = f64::EPSILON * rows.len() as f64 * rows.iter().flatten().fold(0.0, |max, num| num.abs().max(max));
+    (0..rows.len()).all(|i| (0..i).all(|j| (rows[i][j] - rows[j][i]).abs() <= tolerance))
+}
+
+/// `P*A = L*U` with L unit lower triangular, stays exact on fractions.
+pub fn lu(value: &MathType) -> Result<MathType, ExecutionError> {
+    let mut upper = square_rows(value, "lu")?;
+    let size = upper.len();
+    let tolerance = tolerance(&upper);
+    let mut lower = vec![vec![Number::zero(); size]; size];
+    let mut permutation = Matrix::identity(size).to_rows();
+    for col in 0..size {
+        let pivot = pivot_row(&upper, col, col);
+        upper.swap(pivot, col);
+        lower.swap(pivot, col);
+        permutation.swap(pivot, col);
+        if is_negligible(upper[col][col], tolerance) {
+            continue; // singular, the rest of the column is already zero
+        }
+        for row in col + 1..size {
+            lower[row][col] = upper[row][col] / upper[col][col];
+        }
+        eliminate(&mut upper, col, col, true);
+    }
+    for (i, row) in lower.iter_mut().enumerate() {
+        row[i] = Number::one();
+    }
+    Ok(MathType::Tuple(vec![
+        ("L", MathType::Matrix(Matrix::from_rows(&lower))),
+        ("U", MathType::Matrix(Matrix::from_rows(&upper))),
+        ("P", MathType::Matrix(Matrix::from_rows(&permutation))),
+    ]))
+}
+
+// Householder reflections, gives an orthogonal Q and upper triangular R with `A = Q*R`
+fn householder(mut r: Vec<Vec<f64>>) -> (Vec<Vec<f64>>, Vec<Vec<f64>>) {
+    let (height, width) = (r.len(), r.first().map(|row| row.len()).unwrap_or(0));
+    let mut q: Vec<Vec<f64>> = (0..height).map(|i| (0..height).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect();
+    for k in 0..width.min(height.saturating_sub(1)) {
+        let norm = (k..height).map(|i| r[i][k] * r[i][k]).sum::<f64>().sqrt();
+        if norm == 0.0 {
+            continue;
+        }
+        let alpha = if r[k][k] > 0.0 { -norm } else { norm };
+        let mut v: Vec<f64> = (k..height).map(|i| r[i][k]).collect();
+        v[0] -= alpha;
+        let v_norm = v.iter().map(|x| x * x).sum::<f64>().sqrt();
+        v.iter_mut().for_each(|x| *x /= v_norm);
+        let projections: Vec<f64> = (0..width).map(|j| (k..height).map(|i| v[i - k] * r[i][j]).sum()).collect();
+        for (row, v) in r[k..].iter_mut().zip(&v) {
+            row.iter_mut().zip(&projections).for_each(|(num, projection)| *num -= 2.0 * v * projection);
+        }
+        for row in q.iter_mut() {
+            let projection: f64 = (k..height).map(|i| row[i] * v[i - k]).sum();
+            (k..height).for_each(|i| row[i] -= 2.0 * projection * v[i - k]);
+        }
+    }
+    // what's left under the diagonal is rounding error
+    for (i, row) in r.iter_mut().enumerate() {
+        row.iter_mut().take(i).for_each(|num| *num = 0.0);
+    }
+    (q, r)
+}
+
+/// `A = Q*R` by Householder reflections, Q is orthogonal and R upper triangular.
+pub fn qr(value: &MathType) -> Result<MathType, ExecutionError> {
+    let (q, r) = householder(float_rows(value, "qr")?);
+    Ok(MathType::Tuple(vec![("Q", from_float_rows(&q)), ("R", from_float_rows(&r))]))
+}
+
+// the top of R is square and triangular, so solving against Q'b avoids squaring the condition number like A'A would
+fn least_squares(rows: &[Vec<Number>], rhs: &[Vec<Number>]) -> Result<Vec<Vec<Number>>, ExecutionError> {
+    if rows.iter().chain(rhs).flatten().any(|num| num.is_complex()) {
+        return Err(ExecutionError::InvalidOperation("least squares only works on real numbers".to_string()));
+    }
+    let (q, r) = householder(rows.iter().map(|row| row.iter().map(|num| num.to_f64()).collect()).collect());
+    let width = r.first().map(|row| row.len()).unwrap_or(0);
+    let square: Vec<Vec<Number>> = r[..width].iter().map(|row| row.iter().map(|num| Number::from(*num)).collect()).collect();
+    rhs.iter().map(|col| {
+        let projected: Vec<Number> = (0..width)
+            .map(|j| Number::from(iter::zip(&q, col).map(|(row, b)| row[j] * b.to_f64()).sum::<f64>()))
+            .collect();
+        solve(&square, &projected)
+    }).collect()
+}
+
+/// `A \ b` is the x with `A*x = b`, each column of b is solved separately.
+/// Square systems are solved by elimination and tall ones give the least squares fit.
+pub fn left_divide(lhs: &MathType, rhs: &MathType) -> Result<MathType, ExecutionError> {
+    let (Some(a), Some(b)) = (as_matrix(lhs), as_matrix(rhs)) else {
+        return Err(ExecutionError::InvalidOperation(format!("{} \\ {}", lhs.type_name(), rhs.type_name())));
+    };
+    let (height, width, rhs_height) = (a.height(), a.width(), b.height());
+    if rhs_height != height {
+        return Err(ExecutionError::InvalidOperation(format!("can't solve {height} equations with {rhs_height} values on the right")));
+    }
+    let (rows, b) = (a.to_rows(), b.columns().collect::<Vec<_>>());
+    let solution = match height.cmp(&width) {
+        Ordering::Equal => b.iter().map(|col| solve(&rows, col)).collect::<Result<Vec<_>, _>>()?,
+        Ordering::Greater => least_squares(&rows, &b)?,
+        Ordering::Less => return Err(ExecutionError::NotImplemented("solving an underdetermined system".to_string())),
+    };
+    Ok(MathType::from(Matrix::from_columns(&solution)))
+}
+
+/// Upper triangular R with `A = R'*R`, only for symmetric positive definite matrices.
+pub fn chol(value: &MathType) -> Result<MathType, ExecutionError> {
+    let a = float_rows(value, "chol")?;
+    let size = a.len();
+    if a.iter().any(|row| row.len() != size) || !is_symmetric(&a) {
+        return Err(ExecutionError::BadFunctionArgs("chol, expected a symmetric matrix".to_string()));
+    }
+    let mut r = vec![vec![0.0; size]; size];
+    for j in 0..size {
+        let diagonal = a[j][j] - (0..j).map(|k| r[k][j] * r[k][j]).sum::<f64>();
+        if diagonal <= 0.0 {
+            return Err(ExecutionError::BadFunctionArgs("chol, matrix is not positive definite".to_string()));
+        }
+        r[j][j] = diagonal.sqrt();
+        for i in j + 1..size {
+            r[j][i] = (a[j][i] - (0..j).map(|k| r[k][j] * r[k][i]).sum::<f64>()) / r[j][j];
+        }
+    }
+    Ok(from_float_rows(&r))
+}
+
+const MAX_SWEEPS: usize = 100;
+
+// cyclic Jacobi rotations, returns the eigenvalues in ascending order and matching orthonormal eigenvectors as rows
+fn symmetric_eigen(mut a: Vec<Vec<f64>>) -> (Vec<f64>, Vec<Vec<f64>>) {
+    let size = a.len();
+    let mut vectors: Vec<Vec<f64>> = (0..size).map(|i| (0..size).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect();
+    for _ in 0..MAX_SWEEPS {
+        let off_diagonal: f64 = (0..size).flat_map(|i| (0..size).filter(move |j| *j != i).map(move |j| (i, j))).map(|(i, j)| a[i][j] * a[i][j]).sum();
+        let total: f64 = a.iter().flatten().map(|num| num * num).sum();
+        if off_diagonal <= f64::EPSILON * f64::EPSILON * total {
+            break;
+        }
+        for p in 0..size {
+            for q in p + 1..size {
+                if a[p][q] == 0.0 {
+                    continue;
+                }
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for row in a.iter_mut() {
+                    let (kp, kq) = (row[p], row[q]);
+                    row[p] = c * kp - s * kq;
+                    row[q] = s * kp + c * kq;
+                }
+                let (first, second) = a.split_at_mut(q);
+                for (pk, qk) in first[p].iter_mut().zip(second[0].iter_mut()) {
+                    (*pk, *qk) = (c * *pk - s * *qk, s * *pk + c * *qk);
+                }
+                for vector in vectors.iter_mut() {
+                    let (kp, kq) = (vector[p], vector[q]);
+                    vector[p] = c * kp - s * kq;
+                    vector[q] = s * kp + c * kq;
+                }
+            }
+        }
+    }
+    let mut order: Vec<usize> = (0..size).collect();
+    order.sort_by(|i, j| a[*i][*i].total_cmp(&a[*j][*j]));
+    let values = order.iter().map(|i| a[*i][*i]).collect();
+    let columns = order.iter().map(|i| vectors.iter().map(|row| row[*i]).collect()).collect();
+    (values, columns)
+}
+
+// Gram-Schmidt QR on complex entries, used by the general eigenvalue iteration
+fn complex_qr(a: &[Vec<Number>]) -> (Vec<Vec<Number>>, Vec<Vec<Number>>) {
+    let size = a.len();
+    let mut q_columns: Vec<Vec<Number>> = Vec::new();
+    let mut r = vec![vec![Number::zero(); size]; size];
+    for j in 0..size {
+        let mut v: Vec<Number> = a.iter().map(|row| row[j]).collect();
+        for (i, q) in q_columns.iter().enumerate() {
+            let projection: Number = iter::zip(q, &v).map(|(q, v)| q.conj() * *v).sum();
+            r[i][j] = projection;
+            v.iter_mut().zip(q).for_each(|(v, q)| *v = *v - projection * *q);
+        }
+        let norm = v.iter().map(|num| magnitude(*num).powi(2)).sum::<f64>().sqrt();
+        r[j][j] = Number::from(norm);
+        let q = match norm == 0.0 {
+            true => (0..size).map(|i| if i == j { Number::one() } else { Number::zero() }).collect(),
+            false => v.iter().map(|num| *num / Number::from(norm)).collect(),
+        };
+        q_columns.push(q);
+    }
+    (Matrix::from_columns(&q_columns).to_rows(), r)
+}
+
+// shifted QR iteration with deflation, complex arithmetic means complex pairs come out without special cases
+fn general_eigenvalues(rows: &[Vec<Number>]) -> Vec<Number> {
+    let mut a: Vec<Vec<Number>> = rows.iter().map(|row| row.iter().map(|num| num.to_float()).collect()).collect();
+    let mut values = Vec::new();
+    let mut size = a.len();
+    let mut iterations = 0;
+    while size > 0 {
+        let active: Vec<Vec<Number>> = a[..size].iter().map(|row| row[..size].to_vec()).collect();
+        let scale = active.iter().flatten().map(|num| magnitude(*num)).fold(0.0, f64::max);
+        let last_row_small = active[size - 1][..size - 1].iter().all(|num| magnitude(*num) <= f64::EPSILON * scale);
+        if size == 1 || last_row_small || iterations > MAX_SWEEPS * 10 {
+            values.push(active[size - 1][size - 1]);
+            size -= 1;
+            iterations = 0;
+            continue;
+        }
+        iterations += 1;
+        // Wilkinson shift, the eigenvalue of the bottom 2x2 block closest to the bottom corner
+        let (p, q, r, s) = (active[size - 2][size - 2], active[size - 2][size - 1], active[size - 1][size - 2], active[size - 1][size - 1]);
+        let half_trace = (p + s) / Number::from(2);
+        let root = ((p - s) * (p - s) / Number::from(4) + q * r).pow(Number::from(0.5));
+        let shift = match magnitude(half_trace + root - s) < magnitude(half_trace - root - s) {
+            true => half_trace + root,
+            false => half_trace - root,
+        };
+        let shifted: Vec<Vec<Number>> = active.iter().enumerate()
+            .map(|(i, row)| row.iter().enumerate().map(|(j, num)| if i == j { *num - shift } else { *num }).collect())
+            .collect();
+        let (q, r) = complex_qr(&shifted);
+        for i in 0..size {
+            for j in 0..size {
+                let product: Number = (0..size).map(|k| r[i][k] * q[k][j]).sum();
+                a[i][j] = if i == j { product + shift } else { product };
+            }
+        }
+    }
+    values
+}
+
+// inverse iteration, nudging the eigenvalue so the shifted matrix isn't exactly singular
+fn eigenvector(rows: &[Vec<Number>], value: Number) -> Vec<Number> {
+    let size = rows.len();
+    let nudge = value + Number::from(1e-10 * (1.0 + magnitude(value)));
+    let shifted: Vec<Vec<Number>> = rows.iter().enumerate()
+        .map(|(i, row)| row.iter().enumerate().map(|(j, num)| if i == j { *num - nudge } else { *num }).collect())
+        .collect();
+    let mut vector = vec![Number::one(); size];
+    for _ in 0..3 {
+        let next = match solve(&shifted, &vector) {
+            Ok(next) => next,
+            Err(_) => break,
+        };
+        let norm = next.iter().map(|num| magnitude(*num).powi(2)).sum::<f64>().sqrt();
+        // scale to unit length with the biggest entry real and positive
+        let biggest = next.iter().copied().max_by(|a, b| magnitude(*a).total_cmp(&magnitude(*b))).unwrap_or(Number::one());
+        let phase = biggest * Number::from(norm / magnitude(biggest));
+        vector = next.iter().map(|num| *num / phase).collect();
+    }
+    vector
+}
+
+fn clean_eigenvalue(value: Number, scale: f64) -> Number {
+    let (re, im) = value.to_complex();
+    let tolerance = 1e-12 * scale.max(1.0);
+    let re = if re.abs() <= tolerance { 0.0 } else { re };
+    let im = if im.abs() <= tolerance { 0.0 } else { im };
+    Number::complex(re, im)
+}
+
+/// Eigenvalues and eigenvectors, the vectors are the columns of V so `A*V = V*D`.
+pub fn eig(value: &MathType) -> Result<MathType, ExecutionError> {
+    let rows = square_rows(value, "eig")?;
+    let is_real = rows.iter().flatten().all(|num| !num.is_complex());
+    let real_rows: Vec<Vec<f64>> = rows.iter().map(|row| row.iter().map(|num| num.to_f64()).collect()).collect();
+    let (values, columns): (Vec<Number>, Vec<Vec<Number>>) = match is_real && is_symmetric(&real_rows) {
+        true => {
+            let (values, vectors) = symmetric_eigen(real_rows);
+            let columns = vectors.iter().map(|col| col.iter().map(|num| Number::from(*num)).collect()).collect();
+            (values.into_iter().map(Number::from).collect(), columns)
+        },
+        false => {
+            let scale = rows.iter().flatten().map(|num| magnitude(*num)).fold(0.0, f64::max);
+            let mut values: Vec<Number> = general_eigenvalues(&rows).into_iter().map(|value| clean_eigenvalue(value, scale)).collect();
+            values.sort_by(|a, b| {
+                let ((a_re, a_im), (b_re, b_im)) = (a.to_complex(), b.to_complex());
+                a_re.total_cmp(&b_re).then(a_im.total_cmp(&b_im))
+            });
+            let columns = values.iter().map(|value| eigenvector(&rows, *value)).collect();
+            (values, columns)
+        },
+    };
+    Ok(MathType::Tuple(vec![("values", MathType::Matrix(Matrix::column_vector(values))), ("V", MathType::from(Matrix::from_columns(&columns)))]))
+}
+
+type SingularTriple = (f64, Vec<f64>, Vec<f64>); // singular value, left vector, right vector
+
+/// Singular value decomposition `A = U*S*V'` by one-sided Jacobi rotations, S is diagonal and in descending order.
+pub fn svd(value: &MathType) -> Result<MathType, ExecutionError> {
+    let rows = float_rows(value, "svd")?;
+    let (height, width) = (rows.len(), rows.first().map(|row| row.len()).unwrap_or(0));
+    // the rotations work on columns, so a wide matrix is done as its transpose and swapped back at the end
+    let transposed = width > height;
+    let mut u: Vec<Vec<f64>> = match transposed {
+        true => (0..height).map(|col| rows[col].clone()).collect(),
+        false => (0..width).map(|col| rows.iter().map(|row| row[col]).collect()).collect(),
+    };
+    let count = u.len();
+    let mut v: Vec<Vec<f64>> = (0..count).map(|i| (0..count).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect();
+    for _ in 0..MAX_SWEEPS {
+        let mut rotated = false;
+        for p in 0..count {
+            for q in p + 1..count {
+                let alpha: f64 = u[p].iter().map(|x| x * x).sum();
+                let beta: f64 = u[q].iter().map(|x| x * x).sum();
+                let gamma: f64 = iter::zip(&u[p], &u[q]).map(|(x, y)| x * y).sum();
+                if gamma.abs() <= f64::EPSILON * (alpha * beta).sqrt() {
+                    continue;
+                }
+                rotated = true;
+                let zeta = (beta - alpha) / (2.0 * gamma);
+                let t = zeta.signum() / (zeta.abs() + (1.0 + zeta * zeta).sqrt());
+                let c = 1.0 / (1.0 + t * t).sqrt();
+                let s = c * t;
+                for columns in [&mut u, &mut v] {
+                    let (first, second) = columns.split_at_mut(q);
+                    for (x, y) in first[p].iter_mut().zip(second[0].iter_mut()) {
+                        (*x, *y) = (c * *x - s * *y, s * *x + c * *y);
+                    }
+                }
+            }
+        }
+        if !rotated {
+            break;
+        }
+    }
+    let mut singular: Vec<SingularTriple> = iter::zip(u, v).map(|(u, v)| {
+        let sigma = u.iter().map(|x| x * x).sum::<f64>().sqrt();
+        let u = u.iter().map(|x| if sigma == 0.0 { 0.0 } else { x / sigma }).collect();
+        (sigma, u, v)
+    }).collect();
+    singular.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let columns = |pick: fn(&SingularTriple) -> &Vec<f64>| -> Vec<Vec<Number>> {
+        singular.iter().map(|entry| pick(entry).iter().map(|num| Number::from(*num)).collect()).collect()
+    };
+    let (u_columns, v_columns) = match transposed {
+        true => (columns(|entry| &entry.2), columns(|entry| &entry.1)),
+        false => (columns(|entry| &entry.1), columns(|entry| &entry.2)),
+    };
+    let s: Vec<Vec<Number>> = (0..count).map(|i| (0..count).map(|j| Number::from(if i == j { singular[i].0 } else { 0.0 })).collect()).collect();
+    Ok(MathType::Tuple(vec![
+        ("U", MathType::from(Matrix::from_columns(&u_columns))),
+        ("S", MathType::Matrix(Matrix::from_rows(&s))),
+        ("V", MathType::from(Matrix::from_columns(&v_columns))),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{column, matrix};
+
+    #[test]
+    fn determinant_with_pivoting() {
+        assert_eq!(det(&matrix(&[&[0, 1], &[1, 0]])).unwrap(), Number::from(-1));
+        assert_eq!(det(&matrix(&[&[2, 0, 1], &[1, 3, 2], &[1, 1, 2]])).unwrap(), Number::from(6));
+        assert_eq!(det(&matrix(&[&[1, 2], &[2, 4]])).unwrap(), Number::zero());
+        assert!(det(&matrix(&[&[1, 2, 3], &[4, 5, 6]])).is_err());
+    }
+
+    #[test]
+    fn inverse_stays_exact() {
+        let inverse = inv(&matrix(&[&[4, 7], &[2, 6]])).unwrap();
+        let expected = [[Number::ratio(3, 5), Number::ratio(-7, 10)], [Number::ratio(-1, 5), Number::ratio(2, 5)]];
+        match inverse {
+            MathType::Matrix(matrix) => assert_eq!(matrix.to_rows(), expected.map(|row| row.to_vec()).to_vec()),
+            other => panic!("expected a matrix, got {:?}", other),
+        }
+        assert!(matches!(inv(&matrix(&[&[1, 2], &[2, 4]])), Err(ExecutionError::SingularMatrix)));
+    }
+
+    #[test]
+    fn rref_ignores_float_noise() {
+        let rows: Vec<Vec<Number>> = [[0.1, 0.2, 0.3], [0.4, 0.5, 0.6], [0.7, 0.8, 0.9]].iter()
+            .map(|row| row.iter().map(|num| Number::from(*num)).collect())
+            .collect();
+        let reduced = rref(&MathType::Matrix(Matrix::from_rows(&rows))).unwrap();
+        assert_eq!(reduced.to_string(), "[\n\t1, 0, -1, \n\t0, 1, 2, \n\t0, 0, 0, \n]\n");
+    }
+
+    fn parts(tuple: MathType) -> Vec<Vec<Vec<Number>>> {
+        match tuple {
+            MathType::Tuple(parts) => parts.into_iter().map(|(_, part)| as_rows(&part).unwrap()).collect(),
+            other => panic!("expected a tuple, got {:?}", other),
+        }
+    }
+
+    fn product(lhs: &[Vec<Number>], rhs: &[Vec<Number>]) -> Vec<Vec<f64>> {
+        lhs.iter().map(|row| (0..rhs[0].len()).map(|j| {
+            row.iter().enumerate().map(|(k, num)| *num * rhs[k][j]).sum::<Number>().to_f64()
+        }).collect()).collect()
+    }
+
+    fn assert_close(actual: Vec<Vec<f64>>, expected: &[&[f64]]) {
+        for (row, expected) in actual.iter().zip(expected) {
+            for (num, expected) in row.iter().zip(*expected) {
+                assert!((num - expected).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn left_divide_solves_and_fits() {
+        let square = left_divide(&matrix(&[&[2, 1], &[1, 3]]), &column(&[3, 5])).unwrap();
+        assert!(matches!(square, MathType::Matrix(x) if x == Matrix::column_vector(vec![Number::ratio(4, 5), Number::ratio(7, 5)])));
+        // best fit line through (1, 1), (2, 2), (3, 2)
+        let tall = matrix(&[&[1, 1], &[1, 2], &[1, 3]]);
+        let fit = left_divide(&tall, &column(&[1, 2, 2])).unwrap();
+        assert_close(vec![as_matrix(&fit).unwrap().column(0).iter().map(|num| num.to_f64()).collect()], &[&[2.0 / 3.0, 0.5]]);
+        let rhs = column(&[1, 2]);
+        assert!(matches!(left_divide(&matrix(&[&[1, 2], &[2, 4]]), &rhs), Err(ExecutionError::SingularMatrix)));
+        assert!(matches!(left_divide(&tall, &rhs), Err(ExecutionError::InvalidOperation(_))));
+    }
+
+    #[test]
+    fn lu_factors_multiply_back() {
+        let factors = parts(lu(&matrix(&[&[1, 2], &[3, 4]])).unwrap());
+        let (l, u, p) = (&factors[0], &factors[1], &factors[2]);
+        assert_eq!(l[1][0], Number::ratio(1, 3));
+        assert_eq!(product(l, u), product(p, &[vec![Number::from(1), Number::from(2)], vec![Number::from(3), Number::from(4)]]));
+    }
+
+    #[test]
+    fn qr_and_cholesky() {
+        let factors = parts(qr(&matrix(&[&[1, 2], &[3, 4], &[5, 6]])).unwrap());
+        assert_close(product(&factors[0], &factors[1]), &[&[1.0, 2.0], &[3.0, 4.0], &[5.0, 6.0]]);
+        assert_eq!(factors[1][2][1], Number::from(0.0));
+        let r = as_rows(&chol(&matrix(&[&[4, 2], &[2, 3]])).unwrap()).unwrap();
+        assert_close(r.iter().map(|row| row.iter().map(|num| num.to_f64()).collect()).collect(), &[&[2.0, 1.0], &[0.0, 2f64.sqrt()]]);
+        assert!(chol(&matrix(&[&[1, 2], &[2, 1]])).is_err());
+    }
+
+    #[test]
+    fn eigenvalues_real_and_complex() {
+        let symmetric = parts(eig(&matrix(&[&[2, 1], &[1, 2]])).unwrap());
+        assert_close(vec![symmetric[0].iter().map(|row| row[0].to_f64()).collect()], &[&[1.0, 3.0]]);
+        let rotation = parts(eig(&matrix(&[&[0, -1], &[1, 0]])).unwrap());
+        let imaginary: Vec<f64> = rotation[0].iter().map(|row| row[0].im().to_f64()).collect();
+        assert_close(vec![imaginary], &[&[-1.0, 1.0]]);
+        let general = matrix(&[&[4, 1], &[2, 3]]);
+        let factors = parts(eig(&general).unwrap());
+        let vectors = &factors[1];
+        for (i, value) in factors[0].iter().enumerate() {
+            let column: Vec<Vec<Number>> = vectors.iter().map(|row| vec![row[i]]).collect();
+            let scaled: Vec<Vec<f64>> = column.iter().map(|row| vec![(row[0] * value[0]).to_f64()]).collect();
+            let rows = as_rows(&general).unwrap();
+            assert_close(product(&rows, &column), &scaled.iter().map(|row| row.as_slice()).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn singular_values() {
+        let factors = parts(svd(&matrix(&[&[3, 0], &[4, 5]])).unwrap());
+        assert_close(vec![vec![factors[1][0][0].to_f64(), factors[1][1][1].to_f64()]], &[&[45f64.sqrt(), 5f64.sqrt()]]);
+        let wide = parts(svd(&matrix(&[&[1, 2, 3], &[4, 5, 6]])).unwrap());
+        assert_eq!((wide[0].len(), wide[2].len()), (2, 3));
+    }
+}
@@ -1,21 +1,63 @@
 use std::error::Error;
 use std::io::{self, stdin, stdout, Write};
+use std::panic::{self, AssertUnwindSafe};
 use std::process;
+use std::thread;
 
+use tokens::Span;
+
+mod display;
+mod number;
 mod tokens;
 mod tree_builder;
 mod executor;
+mod higher_order;
+mod linear_algebra;
+mod matrix;
 mod operations;
+mod statistics;
+mod units;
+#[cfg(test)]
+mod test_helpers;
 
-fn execute_line(line: &mut String, environment: &mut executor::Environment) -> Result<(), Box<dyn Error>> {
-    get_input(line)?;
+// user functions recurse through the executor, so the interpreter runs on a thread with a bit more room than usual.
+// the executor stops well before this runs out, even in a debug build where every level takes a lot more
+const STACK_SIZE: usize = 64 * 1024 * 1024;
 
-    let mut tokens = tokens::generate_tokens(line);
-    let command_response = tree_builder::parse_commands(&mut tokens, environment)?;
+fn with_interpreter_stack<T: Send>(run: impl FnOnce() -> T + Send) -> T {
+    thread::scope(|scope| {
+        let interpreter = thread::Builder::new().stack_size(STACK_SIZE).spawn_scoped(scope, run).expect("couldn't start the interpreter thread");
+        interpreter.join().unwrap_or_else(|payload| panic::resume_unwind(payload))
+    })
+}
+
+fn report_error(line: &str, error: &dyn Error, span: Option<Span>) {
+    if let Some(span) = span {
+        println!("  {}", span.underline(line)); // offset by the width of the ": " prompt
+    }
+    println!("{}", error);
+}
+
+// runs a single line of input, returning false once the user asks to quit
+fn run_line(line: &str, environment: &mut executor::Environment) -> bool {
+    let mut tokens = match tokens::generate_tokens(line) {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            report_error(line, &e, e.span());
+            return true;
+        },
+    };
+    let command_response = match tree_builder::parse_commands(&mut tokens, environment) {
+        Ok(response) => response,
+        Err(e) => {
+            report_error(line, &e, e.span());
+            return true;
+        },
+    };
 
     let processed = match command_response.as_str() {
         "clear" => true, // todo: clear terminal
-        "exit" => process::exit(0),
+        "exit" => return false,
         "new function" => {
             println!("new function");
             true
@@ -26,24 +68,45 @@ fn execute_line(line: &mut String, environment: &mut executor::Environment) -> R
             true
         },
     };
-    if processed {
-        return Ok(());
+    if processed || tokens.is_empty() {
+        return true;
     }
 
-    let expression_tree = tree_builder::build_expression_tree(tokens);
+    let expression_tree = tree_builder::build_expression_tree(tokens, environment);
     match expression_tree {
-        Ok(nodes) => {
-            // println!("{}", nodes);
-            match executor::execute_expression_tree(&nodes, environment) {
+        Ok(expression) => {
+            // println!("{}", expression);
+            match executor::execute_expression_tree(&expression, environment) {
                 Ok(value) => {
-                    println!("{}\n", value);
+                    println!("{}\n", value.display(environment.number_format()));
                     environment.user_vars.insert("ans".to_string(), value);
                 },
-                Err(e) => println!("{}", e),
+                Err(e) => report_error(line, &e, e.span()),
             }
         },
-        Err(e) => println!("{:?}", e),
+        Err(e) => report_error(line, &e, e.span()),
     };
+    true
+}
+
+fn execute_line(line: &mut String, environment: &mut executor::Environment) -> Result<(), Box<dyn Error>> {
+    if get_input(line)? == 0 {
+        process::exit(0); // end of input
+    }
+
+    // nothing should panic, but if something does it shouldn't take the session with it
+    match panic::catch_unwind(AssertUnwindSafe(|| run_line(line, environment))) {
+        Ok(true) => {},
+        Ok(false) => process::exit(0),
+        Err(payload) => {
+            environment.call_frames.clear(); // a panic partway through a call leaves its frame behind
+            environment.evaluation_depth = 0;
+            let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            println!("internal error: {message}");
+        },
+    }
     Ok(())
 }
 
@@ -52,19 +115,254 @@ fn main() {
 
     let mut environment = executor::Environment::default();
 
-    loop {
+    panic::set_hook(Box::new(|_| {})); // panics are reported by execute_line instead
+
+    with_interpreter_stack(|| loop {
         match execute_line(&mut user_input, &mut environment) {
             Ok(()) => {},
             Err(e) => println!("{:?}", e),
         }
 
         user_input.clear();
-    }
+    })
 }
 
-fn get_input(buf: &mut String) -> Result<(), io::Error> {
+fn get_input(buf: &mut String) -> Result<usize, io::Error> {
     print!(": ");
     stdout().flush()?;
-    stdin().read_line(buf)?;
-    Ok(())
+    stdin().read_line(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // small xorshift generator so the test is repeatable without pulling in a crate
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+        fn pick<'a>(&mut self, options: &[&'a str]) -> &'a str {
+            options[self.next() as usize % options.len()]
+        }
+    }
+
+    // runs an expression like run_line does, but hands back the result so a test can check which error it was
+    fn evaluate(line: &str, environment: &mut executor::Environment) -> Result<String, executor::ExecutionError> {
+        let tokens = tokens::generate_tokens(line).expect("test input tokenizes");
+        let expression = tree_builder::build_expression_tree(tokens, environment).expect("test input parses");
+        executor::execute_expression_tree(&expression, environment).map(|value| value.to_string()).map_err(executor::ExecutionError::unlocated)
+    }
+
+    fn answer(environment: &executor::Environment) -> Option<String> {
+        environment.user_vars.get("ans").map(|value| value.to_string())
+    }
+
+    const FRAGMENTS: &[&str] = &[
+        "0", "1", "2.5", "1e999", "2147483647", "inf", "nan", "x", "y", "ans", "i", "m", "s", "ft", "pi",
+        "+", "-", "*", "/", "\\", "//", "%", "^", ".*", "./", ".^", "=", "=>", "to", "==", "!=", "<", ">=", "&&", "||", "!", "'", "->", "true",
+        "(", ")", "[", "]", ",", ";", "#",
+        "sin", "atan2", "ln", "dot", "cross", "transpose", "det", "inv", "rref", "lu", "qr", "eig", "svd", "mean", "median", "std", "percentile", "cov", "cumsum", "abs", "floor", "rem", "map", "filter", "reduce", "apply",
+        "if", "then", "else", "def g", "g", "mode", "digits", "display", "fixed", "exact", "broadcast", "complex", "polar", "on", "off", "clearvars",
+    ];
+
+    #[test]
+    fn random_input_never_panics() {
+        let mut random = Random(0x2545F4914F6CDD1D);
+        let mut environment = executor::Environment::default();
+        with_interpreter_stack(|| for run in 0..20_000 {
+            let length = 1 + random.next() % 12;
+            let mut line = String::new();
+            for _ in 0..length {
+                line.push_str(random.pick(FRAGMENTS));
+                line.push_str(random.pick(&["", " "]));
+            }
+            run_line(&line, &mut environment);
+            if run % 100 == 0 {
+                environment = executor::Environment::default();
+            }
+        });
+    }
+
+    #[test]
+    fn command_words_are_only_commands_at_the_start() {
+        let mut environment = executor::Environment::default();
+        assert!(run_line("q = 5", &mut environment));
+        run_line("def twice(q) 2q", &mut environment);
+        assert!(matches!(evaluate("twice(q) + exit", &mut environment), Err(executor::ExecutionError::UnknownIdentifier(name)) if name == "exit"));
+        run_line("exit = 1", &mut environment);
+        assert_eq!(evaluate("twice(q) + exit", &mut environment).ok().as_deref(), Some("11"));
+        assert!(!run_line("q", &mut environment));
+    }
+
+    #[test]
+    fn calling_a_variable_says_what_it_is() {
+        let mut environment = executor::Environment::default();
+        run_line("x = 3", &mut environment);
+        assert!(matches!(evaluate("x(2)", &mut environment), Err(executor::ExecutionError::InvalidOperation(message)) if message == "x is a number, not a function"));
+        run_line("sum = 2", &mut environment);
+        assert_eq!(evaluate("sum([1 2])", &mut environment).ok().as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn units_only_multiply_without_brackets() {
+        let mut environment = executor::Environment::default();
+        assert_eq!(evaluate("kg m", &mut environment).ok().as_deref(), Some("1 kg*m"));
+        assert!(matches!(evaluate("h(2)", &mut environment), Err(executor::ExecutionError::UnknownIdentifier(name)) if name == "h"));
+        run_line("def h(t) 2t", &mut environment);
+        assert_eq!(evaluate("h(2)", &mut environment).ok().as_deref(), Some("4"));
+    }
+
+    #[test]
+    fn unit_symbols_are_only_units_next_to_numbers_or_units() {
+        let mut environment = executor::Environment::default();
+        assert_eq!(evaluate("5 m / 2 s", &mut environment).ok().as_deref(), Some("2.5 m/s"));
+        assert_eq!(evaluate("3 ft to m", &mut environment).ok().as_deref(), Some("0.9144 m"));
+        assert_eq!(evaluate("m/s^2", &mut environment).ok().as_deref(), Some("1 m/s^2"));
+        for line in ["m", "m + 1", "sqrt(m)"] {
+            assert!(matches!(evaluate(line, &mut environment), Err(executor::ExecutionError::UnknownIdentifier(name)) if name == "m"));
+        }
+        run_line("m = 3", &mut environment);
+        assert_eq!(evaluate("m + 1", &mut environment).ok().as_deref(), Some("4"));
+    }
+
+    #[test]
+    fn mode_is_a_builtin_inside_expressions() {
+        let mut environment = executor::Environment::default();
+        assert_eq!(evaluate("m = mode([1 2 2 3])", &mut environment).ok().as_deref(), Some("2"));
+        assert_eq!(evaluate("1 + mode([4 4 1])", &mut environment).ok().as_deref(), Some("5"));
+        run_line("def f(v) mode(v)", &mut environment);
+        assert_eq!(evaluate("f([7 3 3])", &mut environment).ok().as_deref(), Some("3"));
+        run_line("mode([5 5 1])", &mut environment);
+        assert_eq!(answer(&environment).as_deref(), Some("5"));
+        run_line("mode rad", &mut environment);
+        assert_eq!(environment.trig_mode, executor::TrigMode::Rad);
+    }
+
+    #[test]
+    fn conditionals_only_run_one_branch() {
+        let mut environment = executor::Environment::default();
+        run_line("def f(x) if x > 0 then x else undefined", &mut environment);
+        run_line("f(3)", &mut environment);
+        assert_eq!(answer(&environment).as_deref(), Some("3"));
+        run_line("f(-3)", &mut environment);
+        assert_eq!(answer(&environment).as_deref(), Some("3"));
+        run_line("if f(2) == 2 then 10 else f(-1)", &mut environment);
+        assert_eq!(answer(&environment).as_deref(), Some("10"));
+    }
+
+    #[test]
+    fn functions_are_values() {
+        let mut environment = executor::Environment::default();
+        run_line("def adder(n) x -> x + n", &mut environment);
+        run_line("def twice(f, x) f(f(x))", &mut environment);
+        for (line, expected) in [
+            ("map(x -> x^2, [1 2; 3 4])", "[\n\t1, 4, \n\t9, 16, \n]\n"),
+            ("filter(x -> x % 2 == 0, [1 2 3 4])", "[2, 4]"),
+            ("reduce((total, x) -> total * x, [1; 2; 3; 4], 1)", "24"),
+            ("apply(col -> max(col) - min(col), [1 5; 4 2])", "[3, 3]"),
+            ("twice(adder(5), 1)", "11"),
+            ("f = (a, b) -> a - b", "(a, b) -> (a - b)"),
+            ("f(1, 3) + reduce(f, [1 2], 10)", "5"),
+        ] {
+            run_line(line, &mut environment);
+            assert_eq!(answer(&environment).as_deref(), Some(expected), "{line}");
+        }
+        assert!(matches!(evaluate("map(adder, [1 2])", &mut environment), Err(executor::ExecutionError::InvalidOperation(_))));
+        assert!(matches!(evaluate("f + 1", &mut environment), Err(executor::ExecutionError::InvalidOperation(_))));
+    }
+
+    #[test]
+    fn errors_inside_higher_order_calls_point_at_the_call() {
+        let mut environment = executor::Environment::default();
+        run_line("def f(x) x + undefinedthing", &mut environment);
+        for line in ["map(f, [1 2])", "2 * map(f, [1 2])", "reduce((a, b) -> f(b), [1 2], 0)", "apply(f, [1 2; 3 4])"] {
+            let tokens = tokens::generate_tokens(line).unwrap();
+            let expression = tree_builder::build_expression_tree(tokens, &environment).unwrap();
+            let call = line.find(|c: char| c.is_alphabetic()).unwrap();
+            let error = executor::execute_expression_tree(&expression, &mut environment).unwrap_err();
+            assert_eq!(error.span(), Some(Span::new(call, line.len())), "{line}");
+            assert!(matches!(error.unlocated(), executor::ExecutionError::UnknownIdentifier(name) if name == "undefinedthing"));
+        }
+    }
+
+    #[test]
+    fn recursion_stops_at_the_depth_limit() {
+        let mut environment = executor::Environment::default();
+        with_interpreter_stack(|| {
+            run_line("def fact(n) if n <= 1 then 1 else n * fact(n - 1)", &mut environment);
+            run_line("fact(10)", &mut environment);
+            assert_eq!(answer(&environment).as_deref(), Some("3628800"));
+            run_line("def forever(n) forever(n + 1)", &mut environment);
+            assert!(matches!(evaluate("forever(0)", &mut environment), Err(executor::ExecutionError::TooMuchRecursion(1000))));
+            assert_eq!(answer(&environment).as_deref(), Some("3628800"));
+            run_line("mode depth 5", &mut environment);
+            assert!(matches!(evaluate("fact(6)", &mut environment), Err(executor::ExecutionError::TooMuchRecursion(5))));
+            assert_eq!(evaluate("fact(5)", &mut environment).ok().as_deref(), Some("120"));
+            assert!(environment.call_frames.is_empty());
+        });
+    }
+
+    #[test]
+    fn functions_only_see_their_own_parameters_and_globals() {
+        let mut environment = executor::Environment::default();
+        for line in ["a = 10", "def double(a) 2a", "def outer(secret) inner() + double(secret)", "def inner() a"] {
+            run_line(line, &mut environment);
+        }
+        run_line("double(3) + a", &mut environment);
+        assert_eq!(answer(&environment).as_deref(), Some("16"));
+        run_line("outer(1)", &mut environment);
+        assert_eq!(answer(&environment).as_deref(), Some("12"));
+        run_line("def leak() secret", &mut environment);
+        run_line("def peek(secret) leak()", &mut environment);
+        assert!(matches!(evaluate("peek(1)", &mut environment), Err(executor::ExecutionError::UnknownIdentifier(name)) if name == "secret"));
+        // the same goes for a function called from inside a call inside another function
+        run_line("def middle(x) leak() + x", &mut environment);
+        run_line("def top(secret) middle(secret)", &mut environment);
+        assert!(matches!(evaluate("top(1)", &mut environment), Err(executor::ExecutionError::UnknownIdentifier(name)) if name == "secret"));
+        assert!(environment.call_frames.is_empty());
+        assert_eq!(environment.user_vars.get("a").map(|value| value.to_string()).as_deref(), Some("10"));
+    }
+
+    #[test]
+    fn deep_function_bodies_run_out_of_room_gracefully() {
+        let mut environment = executor::Environment::default();
+        let body = "(1 * ".repeat(80) + "deep(n - 1)" + &")".repeat(80);
+        with_interpreter_stack(|| {
+            run_line(&format!("def deep(n) if n <= 0 then 0 else {body}"), &mut environment);
+            assert_eq!(evaluate("deep(10)", &mut environment).ok().as_deref(), Some("0"));
+            assert!(matches!(evaluate("deep(999)", &mut environment), Err(executor::ExecutionError::TooMuchRecursion(_))));
+            assert_eq!(environment.evaluation_depth, 0);
+            assert!(environment.call_frames.is_empty());
+        });
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let mut environment = executor::Environment::default();
+        for line in ["(".repeat(100_000), "-".repeat(100_000) + "1", "2".to_string() + &"^2".repeat(100_000)] {
+            let tokens = tokens::generate_tokens(&line).unwrap();
+            assert!(tree_builder::build_expression_tree(tokens, &environment).is_err());
+            assert!(run_line(&line, &mut environment));
+        }
+    }
+
+    #[test]
+    fn long_flat_chains_parse() {
+        let mut environment = executor::Environment::default();
+        with_interpreter_stack(|| {
+            assert_eq!(evaluate(&("1".to_string() + &"+1".repeat(1000)), &mut environment).ok().as_deref(), Some("1001"));
+            assert_eq!(evaluate(&("[1 2]".to_string() + &"'".repeat(1001)), &mut environment).ok().as_deref(), evaluate("[1; 2]", &mut environment).ok().as_deref());
+            let too_deep_to_run = "1".to_string() + &"+1".repeat(9_000);
+            assert!(matches!(evaluate(&too_deep_to_run, &mut environment), Err(executor::ExecutionError::TooMuchRecursion(_))));
+            assert_eq!(environment.evaluation_depth, 0);
+            let tokens = tokens::generate_tokens(&("1".to_string() + &"+1".repeat(1_000_000))).unwrap();
+            assert!(matches!(tree_builder::build_expression_tree(tokens, &environment), Err(tree_builder::ExpressionBuildError::TooLong(_))));
+        });
+    }
 }
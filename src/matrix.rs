@@ -0,0 +1,212 @@
+use std::ops::Index;
+
+use crate::number::Number;
+
+/// A matrix stored row by row in a single buffer, indexed with `matrix[(row, col)]`.
+/// Vectors are matrices too, `[1 2 3]` is a 1x3 row vector and `[1; 2; 3]` is a 3x1 column vector.
+/// The entries are numbers unless it's a mask from a comparison like `x > 2`, which holds bools.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix<T = Number> {
+    height: usize,
+    width: usize,
+    data: Vec<T>,
+}
+
+impl<T: Copy> Matrix<T> {
+    pub fn from_fn(height: usize, width: usize, mut entry: impl FnMut(usize, usize) -> T) -> Self {
+        let data = (0..height * width).map(|i| entry(i / width, i % width)).collect();
+        Self { height, width, data }
+    }
+    /// Rows are assumed to all be the same length.
+    pub fn from_rows(rows: &[Vec<T>]) -> Self {
+        let width = rows.first().map(|row| row.len()).unwrap_or(0);
+        debug_assert!(rows.iter().all(|row| row.len() == width));
+        Self { height: rows.len(), width, data: rows.concat() }
+    }
+    pub fn from_columns(columns: &[Vec<T>]) -> Self {
+        let height = columns.first().map(|col| col.len()).unwrap_or(0);
+        Self::from_fn(height, columns.len(), |row, col| columns[col][row])
+    }
+    pub fn column_vector(vector: Vec<T>) -> Self {
+        Self { height: vector.len(), width: 1, data: vector }
+    }
+    pub fn row_vector(vector: Vec<T>) -> Self {
+        Self { height: 1, width: vector.len(), data: vector }
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn is_vector(&self) -> bool {
+        self.height == 1 || self.width == 1
+    }
+    /// Every entry, row by row, which for a vector is just its values in order.
+    pub fn entries(&self) -> &[T] {
+        &self.data
+    }
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // chunks can't have a size of 0, but then there's no data to split anyway
+        self.data.chunks(self.width.max(1))
+    }
+    pub fn column(&self, col: usize) -> Vec<T> {
+        self.rows().map(|row| row[col]).collect()
+    }
+    pub fn columns(&self) -> impl Iterator<Item = Vec<T>> + '_ {
+        (0..self.width).map(|col| self.column(col))
+    }
+    pub fn to_rows(&self) -> Vec<Vec<T>> {
+        self.rows().map(<[T]>::to_vec).collect()
+    }
+
+    pub fn map<U>(&self, function: impl Fn(T) -> U) -> Matrix<U> {
+        Matrix { height: self.height, width: self.width, data: self.data.iter().copied().map(function).collect() }
+    }
+    /// Applies `function` to each pair of entries, or None if the shapes don't match.
+    pub fn zip_with<U>(&self, other: &Matrix<T>, function: impl Fn(T, T) -> U) -> Option<Matrix<U>> {
+        match (self.height, self.width) == (other.height, other.width) {
+            true => Some(Matrix { height: self.height, width: self.width, data: self.data.iter().zip(&other.data).map(|(l, r)| function(*l, *r)).collect() }),
+            false => None,
+        }
+    }
+    /// Repeats a single row or column until the matrix is `height` by `width`, sizes that already match are left alone.
+    pub fn repeat_to(&self, height: usize, width: usize) -> Self {
+        Self::from_fn(height, width, |row, col| self[(row % self.height, col % self.width)])
+    }
+    pub fn transpose(&self) -> Self {
+        Self::from_fn(self.width, self.height, |row, col| self[(col, row)])
+    }
+}
+
+impl Matrix {
+    pub fn identity(size: usize) -> Self {
+        Self::from_fn(size, size, |row, col| if row == col { Number::one() } else { Number::zero() })
+    }
+
+    fn floats(&self) -> Option<Vec<f64>> {
+        self.data.iter().map(|num| match num {
+            Number::Float(num) => Some(*num),
+            _ => None,
+        }).collect()
+    }
+
+    /// Matrix product, or None if the width of self doesn't match the height of `rhs`.
+    pub fn product(&self, rhs: &Matrix) -> Option<Self> {
+        if self.width != rhs.height {
+            return None;
+        }
+        let shape = (self.height, self.width, rhs.width);
+        let data = match (self.floats(), rhs.floats()) {
+            // with nothing but floats there's no need to check for fractions or complex numbers on every step
+            (Some(lhs), Some(rhs_floats)) => row_sums(&lhs, &rhs_floats, shape, 0.0, |total, scale, num| total + scale * num)
+                .into_iter()
+                .map(Number::Float)
+                .collect(),
+            _ => row_sums(&self.data, &rhs.data, shape, Number::zero(), |total, scale, num| total + scale * num),
+        };
+        Some(Self { height: self.height, width: rhs.width, data })
+    }
+    /// Multiplies a square matrix by itself `exponent` times, squaring as it goes so big powers only take a few products.
+    pub fn power(&self, mut exponent: u64) -> Self {
+        let (mut result, mut base) = (Self::identity(self.height), self.clone());
+        while exponent > 0 {
+            if exponent % 2 == 1 {
+                result = result.product(&base).expect("powers are only taken of square matrices");
+            }
+            exponent /= 2;
+            if exponent > 0 {
+                base = base.product(&base).expect("powers are only taken of square matrices");
+            }
+        }
+        result
+    }
+}
+
+// each row of a product is a sum of rows of rhs scaled by one row of lhs, so every loop walks along memory in order
+fn row_sums<T: Copy>(lhs: &[T], rhs: &[T], (height, inner, width): (usize, usize, usize), zero: T, step: impl Fn(T, T, T) -> T) -> Vec<T> {
+    let mut data = vec![zero; height * width];
+    for (result, row) in data.chunks_mut(width.max(1)).zip(lhs.chunks(inner.max(1))) {
+        for (scale, rhs_row) in row.iter().zip(rhs.chunks(width.max(1))) {
+            for (total, num) in result.iter_mut().zip(rhs_row) {
+                *total = step(*total, *scale, *num);
+            }
+        }
+    }
+    data
+}
+
+impl<T> Index<(usize, usize)> for Matrix<T> {
+    type Output = T;
+    fn index(&self, (row, col): (usize, usize)) -> &T {
+        &self.data[row * self.width + col]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::matrix;
+
+    #[test]
+    fn shape_and_layout() {
+        let a: Matrix = matrix(&[&[1, 2, 3], &[4, 5, 6]]);
+        assert_eq!((a.height(), a.width()), (2, 3));
+        assert_eq!(a[(1, 0)], Number::from(4));
+        assert_eq!(a.column(2), [Number::from(3), Number::from(6)]);
+        assert_eq!(Matrix::from_columns(&a.columns().collect::<Vec<_>>()), a);
+        assert_eq!(a.rows().nth(1), Some([4, 5, 6].map(Number::from).as_slice()));
+        assert_eq!(a.transpose(), matrix(&[&[1, 4], &[2, 5], &[3, 6]]));
+        assert!(Matrix::row_vector(a.column(0)).is_vector() && !a.is_vector());
+        assert_eq!(Matrix::row_vector(a.column(1)).repeat_to(3, 2), matrix(&[&[2, 5], &[2, 5], &[2, 5]]));
+    }
+
+    #[test]
+    fn products() {
+        let a: Matrix = matrix(&[&[1, 2], &[3, 4]]);
+        assert_eq!(a.product(&matrix(&[&[0, 1], &[1, 0]])), Some(matrix(&[&[2, 1], &[4, 3]])));
+        assert_eq!(a.product(&Matrix::identity(2)), Some(a.clone()));
+        assert_eq!(a.product(&Matrix::column_vector(vec![Number::from(1), Number::from(1)])), Some(matrix(&[&[3], &[7]])));
+        assert_eq!(a.product(&matrix(&[&[1, 2]])), None);
+        assert_eq!(a.power(0), Matrix::identity(2));
+        assert_eq!(a.power(5), a.product(&a).and_then(|a2| a2.product(&a2)).and_then(|a4| a4.product(&a)).unwrap());
+        let floats = a.map(Number::to_float);
+        assert_eq!(floats.product(&floats), a.product(&a).map(|exact| exact.map(Number::to_float)));
+    }
+
+    // the column-major Vec<Vec> product this replaced, kept around to compare against
+    fn nested_product(lhs: &[Vec<Number>], rhs: &[Vec<Number>]) -> Vec<Vec<Number>> {
+        let (width1, height1) = (lhs.len(), lhs.first().map(|v| v.len()).unwrap_or(0));
+        let width2 = rhs.len();
+        let mut matrix: Vec<Vec<Number>> = Vec::new();
+        let mut row1 = 0;
+        while row1 < height1 {
+            let mut i = 0;
+            while i < width2 {
+                let mut dotsum = Number::zero();
+                let mut col1 = 0;
+                while col1 < width1 {
+                    dotsum = dotsum + lhs[col1][row1] * rhs[i][col1];
+                    col1 += 1;
+                }
+                match matrix.get_mut(i) {
+                    Some(vector) => vector.push(dotsum),
+                    None => matrix.push(vec![dotsum]),
+                }
+                i += 1;
+            }
+            row1 += 1;
+        }
+        matrix
+    }
+
+    #[test]
+    fn flat_product_matches_nested_layout() {
+        let size = 60;
+        let a = Matrix::from_fn(size, size, |row, col| Number::from(((row * 7 + col * 13) % 17) as f64 / 3.0));
+        let b = a.transpose();
+        let (nested_a, nested_b): (Vec<_>, Vec<_>) = (a.columns().collect(), b.columns().collect());
+        assert_eq!(Matrix::from_columns(&nested_product(&nested_a, &nested_b)), a.product(&b).unwrap());
+    }
+}
@@ -0,0 +1,461 @@
+use std::fmt;
+use std::iter::Sum;
+use std::num::ParseFloatError;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::str::FromStr;
+
+/// A number that stays an exact fraction for as long as it can. Anything irrational, or a fraction that no
+/// longer fits in 64 bits, falls back to a float, and anything with an imaginary part is a complex float.
+#[derive(Debug, Clone, Copy)]
+pub enum Number {
+    Ratio(i64, i64), // numerator, denominator, always reduced with a positive denominator
+    Float(f64),
+    Complex(f64, f64), // real, imaginary, the imaginary part is never 0
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.abs()
+}
+
+impl Number {
+    pub const INFINITY: Number = Number::Float(f64::INFINITY);
+    pub const NAN: Number = Number::Float(f64::NAN);
+
+    pub fn zero() -> Number {
+        Number::Ratio(0, 1)
+    }
+
+    pub fn one() -> Number {
+        Number::Ratio(1, 1)
+    }
+
+    /// Builds a reduced fraction, falling back to a float if it doesn't fit or divides by zero.
+    pub fn ratio(numerator: i128, denominator: i128) -> Number {
+        if denominator == 0 {
+            return Number::Float(numerator as f64 / 0.0);
+        }
+        let divisor = gcd(numerator, denominator) * denominator.signum();
+        let (numerator, denominator) = (numerator / divisor, denominator / divisor);
+        match (i64::try_from(numerator), i64::try_from(denominator)) {
+            (Ok(numerator), Ok(denominator)) => Number::Ratio(numerator, denominator),
+            _ => Number::Float(numerator as f64 / denominator as f64),
+        }
+    }
+
+    /// Builds a complex number, which is just a float if the imaginary part is 0.
+    pub fn complex(re: f64, im: f64) -> Number {
+        match im == 0.0 {
+            true => Number::Float(re),
+            false => Number::Complex(re, im),
+        }
+    }
+
+    fn from_polar(magnitude: f64, angle: f64) -> Number {
+        Number::complex(magnitude * angle.cos(), magnitude * angle.sin())
+    }
+
+    /// The real value, which is NaN for complex numbers since they can't be used as one.
+    pub fn to_f64(self) -> f64 {
+        match self {
+            Number::Ratio(numerator, denominator) => numerator as f64 / denominator as f64,
+            Number::Float(num) => num,
+            Number::Complex(..) => f64::NAN,
+        }
+    }
+
+    pub fn to_complex(self) -> (f64, f64) {
+        match self {
+            Number::Complex(re, im) => (re, im),
+            num => (num.to_f64(), 0.0),
+        }
+    }
+
+    pub fn to_float(self) -> Number {
+        match self {
+            Number::Complex(..) => self,
+            num => Number::Float(num.to_f64()),
+        }
+    }
+
+    pub fn is_complex(self) -> bool {
+        matches!(self, Number::Complex(..))
+    }
+
+    pub fn is_exact(self) -> bool {
+        matches!(self, Number::Ratio(..))
+    }
+
+    pub fn as_integer(self) -> Option<i64> {
+        match self {
+            Number::Ratio(numerator, 1) => Some(numerator),
+            Number::Float(num) if num.fract() == 0.0 && num.abs() < i64::MAX as f64 => Some(num as i64),
+            _ => None,
+        }
+    }
+
+    pub fn re(self) -> Number {
+        match self {
+            Number::Complex(re, _) => Number::Float(re),
+            num => num,
+        }
+    }
+
+    pub fn im(self) -> Number {
+        match self {
+            Number::Complex(_, im) => Number::Float(im),
+            _ => Number::zero(),
+        }
+    }
+
+    pub fn conj(self) -> Number {
+        match self {
+            Number::Complex(re, im) => Number::Complex(re, -im),
+            num => num,
+        }
+    }
+
+    /// Magnitude, which stays exact for fractions.
+    pub fn abs(self) -> Number {
+        match self {
+            Number::Ratio(numerator, denominator) => Number::ratio((numerator as i128).abs(), denominator as i128),
+            Number::Float(num) => Number::Float(num.abs()),
+            Number::Complex(re, im) => Number::Float(re.hypot(im)),
+        }
+    }
+
+    /// Angle from the positive real axis in radians.
+    pub fn arg(self) -> f64 {
+        let (re, im) = self.to_complex();
+        im.atan2(re)
+    }
+
+    /// Natural log, negative and complex numbers give the principal complex value.
+    pub fn ln(self) -> Number {
+        match self {
+            Number::Complex(..) => Number::complex(self.abs().to_f64().ln(), self.arg()),
+            num if num.to_f64() < 0.0 => Number::complex((-num.to_f64()).ln(), self.arg()),
+            num => Number::Float(num.to_f64().ln()),
+        }
+    }
+
+    pub fn exp(self) -> Number {
+        let (re, im) = self.to_complex();
+        Number::from_polar(re.exp(), im)
+    }
+
+    pub fn recip(self) -> Number {
+        Number::one() / self
+    }
+
+    /// Applies a float function, used for anything transcendental.
+    pub fn map_float(self, function: impl Fn(f64) -> f64) -> Number {
+        Number::Float(function(self.to_f64()))
+    }
+
+    pub fn pow(self, exponent: Number) -> Number {
+        if let (Number::Ratio(numerator, denominator), Number::Ratio(power, 1)) = (self, exponent) {
+            let exact = i32::try_from(power.unsigned_abs()).ok().and_then(|power| {
+                let numerator = (numerator as i128).checked_pow(power as u32)?;
+                let denominator = (denominator as i128).checked_pow(power as u32)?;
+                Some(Number::ratio(numerator, denominator))
+            });
+            match (exact, power < 0) {
+                (Some(result), false) if result.is_exact() => return result,
+                (Some(result), true) if result.is_exact() => return result.recip(),
+                _ => {},
+            }
+        }
+        let (base, power) = (self.to_f64(), exponent.to_f64());
+        let needs_complex = self.is_complex() || exponent.is_complex() || (base < 0.0 && power.fract() != 0.0);
+        match needs_complex && !self.is_zero() {
+            // principal value of z^w = e^(w ln z)
+            true => (exponent * self.ln()).exp(),
+            false => Number::Float(base.powf(power)),
+        }
+    }
+
+    // rounds to a whole number, exactly for fractions and part by part for complex numbers
+    fn round_with(self, ratio: impl Fn(i128, i128) -> i128, float: impl Fn(f64) -> f64) -> Number {
+        match self {
+            Number::Ratio(numerator, denominator) => Number::ratio(ratio(numerator as i128, denominator as i128), 1),
+            Number::Float(num) => Number::Float(float(num)),
+            Number::Complex(re, im) => Number::complex(float(re), float(im)),
+        }
+    }
+
+    pub fn floor(self) -> Number {
+        self.round_with(|n, d| n.div_euclid(d), f64::floor)
+    }
+
+    pub fn ceil(self) -> Number {
+        self.round_with(|n, d| -(-n).div_euclid(d), f64::ceil)
+    }
+
+    pub fn trunc(self) -> Number {
+        self.round_with(|n, d| n / d, f64::trunc)
+    }
+
+    /// Rounds to the nearest whole number, halves go away from zero.
+    pub fn round(self) -> Number {
+        self.round_with(|n, d| n.signum() * ((2 * n.abs() + d) / (2 * d)), f64::round)
+    }
+
+    /// Floored division, so `-7 // 2` is `-4`.
+    pub fn div_floor(self, rhs: Number) -> Number {
+        (self / rhs).floor()
+    }
+
+    /// Floored modulo, which takes the sign of `rhs` so `-7 % 3` is `2`.
+    pub fn modulo(self, rhs: Number) -> Number {
+        self - rhs * self.div_floor(rhs)
+    }
+
+    /// Truncated remainder, which takes the sign of `self` so `rem(-7, 3)` is `-1`.
+    pub fn rem(self, rhs: Number) -> Number {
+        self - rhs * (self / rhs).trunc()
+    }
+
+    pub fn is_zero(self) -> bool {
+        match self {
+            Number::Ratio(numerator, _) => numerator == 0,
+            Number::Float(num) => num == 0.0,
+            Number::Complex(..) => false,
+        }
+    }
+
+    fn as_fraction(self) -> Option<(i128, i128)> {
+        match self {
+            Number::Ratio(numerator, denominator) => Some((numerator as i128, denominator as i128)),
+            _ => None,
+        }
+    }
+}
+
+// parses a decimal literal like "0.125" or "3e4" into an exact fraction when it fits
+fn parse_exact(text: &str) -> Option<Number> {
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i32>().ok()?),
+        None => (text, 0),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{whole}{fraction}");
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let numerator: i128 = digits.parse().ok()?;
+    let scale = exponent.checked_sub(fraction.len() as i32)?;
+    let power = 10i128.checked_pow(scale.unsigned_abs())?;
+    let number = match scale >= 0 {
+        true => Number::ratio(numerator.checked_mul(power)?, 1),
+        false => Number::ratio(numerator, power),
+    };
+    number.is_exact().then_some(number)
+}
+
+impl FromStr for Number {
+    type Err = ParseFloatError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match parse_exact(text) {
+            Some(number) => Ok(number),
+            None => text.parse::<f64>().map(Number::Float),
+        }
+    }
+}
+
+impl From<i64> for Number {
+    fn from(num: i64) -> Self {
+        Number::Ratio(num, 1)
+    }
+}
+
+impl From<f64> for Number {
+    fn from(num: f64) -> Self {
+        Number::Float(num)
+    }
+}
+
+// exact fractions compare exactly, anything else compares by float value
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Number::Ratio(a, b), Number::Ratio(c, d)) => a == c && b == d,
+            _ => self.to_complex() == other.to_complex(),
+        }
+    }
+}
+
+// complex numbers have no order, so comparing with one gives None
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match (self.as_fraction(), other.as_fraction()) {
+            (Some((a, b)), Some((c, d))) => (a * d).partial_cmp(&(c * b)),
+            _ => self.to_f64().partial_cmp(&other.to_f64()),
+        }
+    }
+}
+
+impl Add for Number {
+    type Output = Number;
+
+    fn add(self, rhs: Number) -> Number {
+        // plain floats are by far the most common case, so they skip the checks below
+        if let (Number::Float(a), Number::Float(b)) = (self, rhs) {
+            return Number::Float(a + b);
+        }
+        if let (Some((a, b)), Some((c, d))) = (self.as_fraction(), rhs.as_fraction()) {
+            if let Some(numerator) = (a * d).checked_add(c * b) {
+                return Number::ratio(numerator, b * d);
+            }
+        }
+        let ((a, b), (c, d)) = (self.to_complex(), rhs.to_complex());
+        Number::complex(a + c, b + d)
+    }
+}
+
+impl Sub for Number {
+    type Output = Number;
+
+    fn sub(self, rhs: Number) -> Number {
+        self + (-rhs)
+    }
+}
+
+impl Mul for Number {
+    type Output = Number;
+
+    fn mul(self, rhs: Number) -> Number {
+        if let (Number::Float(a), Number::Float(b)) = (self, rhs) {
+            return Number::Float(a * b);
+        }
+        match (self.as_fraction(), rhs.as_fraction()) {
+            (Some((a, b)), Some((c, d))) => Number::ratio(a * c, b * d),
+            _ if self.is_complex() || rhs.is_complex() => {
+                let ((a, b), (c, d)) = (self.to_complex(), rhs.to_complex());
+                Number::complex(a * c - b * d, a * d + b * c)
+            },
+            _ => Number::Float(self.to_f64() * rhs.to_f64()),
+        }
+    }
+}
+
+impl Div for Number {
+    type Output = Number;
+
+    fn div(self, rhs: Number) -> Number {
+        match (self.as_fraction(), rhs.as_fraction()) {
+            (Some((a, b)), Some((c, d))) => Number::ratio(a * d, b * c),
+            _ if rhs.is_complex() => {
+                let (c, d) = rhs.to_complex();
+                self * Number::Complex(c, -d) / Number::Float(c * c + d * d)
+            },
+            _ if self.is_complex() => {
+                let ((a, b), divisor) = (self.to_complex(), rhs.to_f64());
+                Number::complex(a / divisor, b / divisor)
+            },
+            _ => Number::Float(self.to_f64() / rhs.to_f64()),
+        }
+    }
+}
+
+impl Neg for Number {
+    type Output = Number;
+
+    fn neg(self) -> Number {
+        match self {
+            Number::Ratio(numerator, denominator) => Number::ratio(-(numerator as i128), denominator as i128),
+            Number::Float(num) => Number::Float(-num),
+            Number::Complex(re, im) => Number::Complex(-re, -im),
+        }
+    }
+}
+
+impl Sum for Number {
+    fn sum<I: Iterator<Item = Number>>(iter: I) -> Number {
+        iter.fold(Number::zero(), |total, num| total + num)
+    }
+}
+
+// plain decimal form, fractions are only shown when printing results (see display.rs)
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::Ratio(numerator, 1) => write!(f, "{numerator}"),
+            Number::Complex(re, im) => write!(f, "{re}{im:+}i"),
+            _ => write!(f, "{}", self.to_f64()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exact(text: &str) -> Number {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn literals_parse_exactly() {
+        assert_eq!(exact("0.1"), Number::Ratio(1, 10));
+        assert_eq!(exact("2.50"), Number::Ratio(5, 2));
+        assert_eq!(exact("3e4"), Number::Ratio(30000, 1));
+        assert_eq!(exact("1e-3"), Number::Ratio(1, 1000));
+        assert!(!exact("1e-20").is_exact());
+        assert!(!exact("6.022e23").is_exact());
+    }
+
+    #[test]
+    fn arithmetic_stays_exact() {
+        let third = Number::ratio(1, 3);
+        let sixth = Number::ratio(1, 6);
+        assert_eq!(third + sixth, Number::Ratio(1, 2));
+        assert_eq!(third - sixth, Number::Ratio(1, 6));
+        assert_eq!(third * sixth, Number::Ratio(1, 18));
+        assert_eq!(third / sixth, Number::Ratio(2, 1));
+        assert_eq!(-third, Number::Ratio(-1, 3));
+        assert_eq!(Number::ratio(2, 3).pow(Number::from(-2)), Number::Ratio(9, 4));
+    }
+
+    #[test]
+    fn falls_back_to_float() {
+        assert!(!(Number::ratio(1, 3) + Number::Float(0.5)).is_exact());
+        assert!(!Number::from(2).pow(Number::ratio(1, 2)).is_exact());
+        assert!(!Number::from(i64::MAX).pow(Number::from(3)).is_exact());
+        assert_eq!(Number::from(1) / Number::zero(), Number::INFINITY);
+    }
+
+    #[test]
+    fn rounding_and_division() {
+        let (seven, three) = (Number::from(7), Number::from(3));
+        assert_eq!((-seven).div_floor(Number::from(2)), Number::from(-4));
+        assert_eq!((-seven).modulo(three), Number::from(2));
+        assert_eq!(seven.modulo(-three), Number::from(-2));
+        assert_eq!((-seven).rem(three), Number::from(-1));
+        assert_eq!(Number::ratio(5, 2).round(), Number::from(3));
+        assert_eq!(Number::ratio(-5, 2).round(), Number::from(-3));
+        assert_eq!(Number::ratio(-5, 2).ceil(), Number::from(-2));
+        assert_eq!(Number::ratio(-5, 2).trunc(), Number::from(-2));
+        assert_eq!(Number::from(-2.5).floor(), Number::from(-3.0));
+        assert_eq!(Number::from(7.5).modulo(Number::from(2.0)), Number::from(1.5));
+    }
+
+    fn close(a: Number, b: Number) -> bool {
+        let ((a, b), (c, d)) = (a.to_complex(), b.to_complex());
+        (a - c).abs() < 1e-12 && (b - d).abs() < 1e-12
+    }
+
+    #[test]
+    fn complex_arithmetic() {
+        let i = Number::complex(0.0, 1.0);
+        assert_eq!(i * i, Number::from(-1.0));
+        assert_eq!(Number::from(1) / i, Number::complex(0.0, -1.0));
+        assert_eq!((Number::from(3) + Number::from(4) * i).abs(), Number::from(5.0));
+        assert!(close(Number::from(-1).ln(), Number::complex(0.0, std::f64::consts::PI)));
+        assert!(close(Number::from(-8).pow(Number::ratio(1, 3)), Number::complex(1.0, 3f64.sqrt())));
+        assert!(close(i.pow(Number::from(2)), Number::from(-1)));
+    }
+}
@@ -1,213 +1,464 @@
 use std::iter;
 
-use crate::executor::{MathType, ExecutionError};
+use crate::executor::{Environment, ExecutionError, MathType, TrigMode};
+use crate::higher_order;
+use crate::linear_algebra;
+use crate::matrix::Matrix;
+use crate::number::Number;
+use crate::statistics;
+use crate::units;
 
-pub fn execute_builtin_function(fname: &str, args: Vec<MathType>) -> Result<MathType, ExecutionError> {
-    match fname {
-        "sin" => match args.get(0) {
-            Some(MathType::Number(num)) => Ok(MathType::Number(num.sin())),
-            _ => Err(ExecutionError::BadFunctionArgs("sin".to_string()))
+fn angle_to_radians(angle: f64, trig_mode: &TrigMode) -> f64 {
+    match trig_mode {
+        TrigMode::Rad => angle,
+        TrigMode::Deg => angle.to_radians(),
+    }
+}
+
+fn angle_from_radians(radians: f64, trig_mode: &TrigMode) -> f64 {
+    match trig_mode {
+        TrigMode::Rad => radians,
+        TrigMode::Deg => radians.to_degrees(),
+    }
+}
+
+// in degree mode multiples of 90 are exact, so sin(180) is 0 rather than 1.2e-16
+fn sin(angle: f64, trig_mode: &TrigMode) -> f64 {
+    if let TrigMode::Deg = trig_mode {
+        match angle.rem_euclid(360.0) {
+            0.0 | 180.0 => return 0.0,
+            90.0 => return 1.0,
+            270.0 => return -1.0,
+            _ => {},
         }
-        "cos" => match args.get(0) {
-            Some(MathType::Number(num)) => Ok(MathType::Number(num.cos())),
-            _ => Err(ExecutionError::BadFunctionArgs("cos".to_string()))
-        },
-        "tan" => match args.get(0) {
-            Some(MathType::Number(num)) => Ok(MathType::Number(num.tan())),
-            _ => Err(ExecutionError::BadFunctionArgs("cos".to_string()))
+    }
+    angle_to_radians(angle, trig_mode).sin()
+}
+
+fn cos(angle: f64, trig_mode: &TrigMode) -> f64 {
+    if let TrigMode::Deg = trig_mode {
+        match angle.rem_euclid(360.0) {
+            0.0 => return 1.0,
+            90.0 | 270.0 => return 0.0,
+            180.0 => return -1.0,
+            _ => {},
+        }
+    }
+    angle_to_radians(angle, trig_mode).cos()
+}
+
+fn tan(angle: f64, trig_mode: &TrigMode) -> f64 {
+    if let TrigMode::Deg = trig_mode {
+        match angle.rem_euclid(180.0) {
+            0.0 => return 0.0,
+            90.0 => return f64::INFINITY,
+            _ => {},
+        }
+    }
+    angle_to_radians(angle, trig_mode).tan()
+}
+
+// anything transcendental works on floats, so these results are never exact
+fn number_function(fname: &str, args: &[MathType], function: impl Fn(f64) -> f64) -> Result<MathType, ExecutionError> {
+    match args {
+        [MathType::Number(num)] if num.is_complex() => Err(ExecutionError::BadFunctionArgs(format!("{fname} of a complex number"))),
+        [MathType::Number(num)] => Ok(MathType::Number(num.map_float(function))),
+        _ => Err(ExecutionError::BadFunctionArgs(fname.to_string())),
+    }
+}
+
+// applies to every entry of a vector or matrix as well as plain numbers
+fn elementwise_function(fname: &str, args: &[MathType], function: impl Fn(Number) -> Number) -> Result<MathType, ExecutionError> {
+    match args {
+        [MathType::Number(num)] => Ok(MathType::Number(function(*num))),
+        [MathType::Matrix(matrix)] => Ok(MathType::Matrix(matrix.map(function))),
+        _ => Err(ExecutionError::BadFunctionArgs(fname.to_string())),
+    }
+}
+
+pub fn execute_builtin_function(fname: &str, args: Vec<MathType>, environment: &mut Environment) -> Result<MathType, ExecutionError> {
+    let mode = &environment.trig_mode;
+    match fname {
+        "sin" => number_function(fname, &args, |num| sin(num, mode)),
+        "cos" => number_function(fname, &args, |num| cos(num, mode)),
+        "tan" => number_function(fname, &args, |num| tan(num, mode)),
+        "sec" => number_function(fname, &args, |num| 1.0 / cos(num, mode)),
+        "csc" => number_function(fname, &args, |num| 1.0 / sin(num, mode)),
+        "cot" => number_function(fname, &args, |num| cos(num, mode) / sin(num, mode)),
+        "asin" => number_function(fname, &args, |num| angle_from_radians(num.asin(), mode)),
+        "acos" => number_function(fname, &args, |num| angle_from_radians(num.acos(), mode)),
+        "atan" => number_function(fname, &args, |num| angle_from_radians(num.atan(), mode)),
+        "asec" => number_function(fname, &args, |num| angle_from_radians(num.recip().acos(), mode)),
+        "acsc" => number_function(fname, &args, |num| angle_from_radians(num.recip().asin(), mode)),
+        "acot" => number_function(fname, &args, |num| angle_from_radians(num.recip().atan(), mode)),
+        "atan2" => match args.as_slice() {
+            [MathType::Number(y), MathType::Number(x)] if !y.is_complex() && !x.is_complex() => Ok(MathType::Number(Number::from(angle_from_radians(y.to_f64().atan2(x.to_f64()), mode)))),
+            _ => Err(ExecutionError::BadFunctionArgs("atan2".to_string())),
         },
-        "ln" => match args.get(0) {
-            Some(MathType::Number(num)) => Ok(MathType::Number(num.ln())),
-            _ => Err(ExecutionError::BadFunctionArgs("ln".to_string())),
+        "sinh" => number_function(fname, &args, |num| num.sinh()),
+        "cosh" => number_function(fname, &args, |num| num.cosh()),
+        "tanh" => number_function(fname, &args, |num| num.tanh()),
+        "sech" => number_function(fname, &args, |num| num.cosh().recip()),
+        "csch" => number_function(fname, &args, |num| num.sinh().recip()),
+        "coth" => number_function(fname, &args, |num| num.tanh().recip()),
+        "asinh" => number_function(fname, &args, |num| num.asinh()),
+        "acosh" => number_function(fname, &args, |num| num.acosh()),
+        "atanh" => number_function(fname, &args, |num| num.atanh()),
+        "asech" => number_function(fname, &args, |num| num.recip().acosh()),
+        "acsch" => number_function(fname, &args, |num| num.recip().asinh()),
+        "acoth" => number_function(fname, &args, |num| num.recip().atanh()),
+        "ln" => elementwise_function(fname, &args, Number::ln),
+        "floor" => elementwise_function(fname, &args, Number::floor),
+        "ceil" => elementwise_function(fname, &args, Number::ceil),
+        "round" => elementwise_function(fname, &args, Number::round),
+        "trunc" => elementwise_function(fname, &args, Number::trunc),
+        "rem" => match args.as_slice() {
+            [MathType::Number(lhs), MathType::Number(rhs)] => Ok(MathType::Number(lhs.rem(*rhs))),
+            _ => Err(ExecutionError::BadFunctionArgs("rem".to_string())),
         },
-        "dot" => match (args.get(0), args.get(1)) {
-            (Some(MathType::Vector(vec1)), Some(MathType::Vector(vec2))) => match vec1.len() == vec2.len() {
-                true => Ok(MathType::Number(iter::zip(vec1, vec2).map(|(num1, num2)| num1 * num2).sum())),
-                false => Err(ExecutionError::BadFunctionArgs("dot with unequal vector lengths".to_string())),
+        "re" => elementwise_function(fname, &args, Number::re),
+        "im" => elementwise_function(fname, &args, Number::im),
+        "abs" => elementwise_function(fname, &args, Number::abs),
+        "conj" => elementwise_function(fname, &args, Number::conj),
+        "arg" => elementwise_function(fname, &args, |num| Number::from(angle_from_radians(num.arg(), mode))),
+        // rows and columns both work here, only the values matter
+        "dot" => match args.as_slice() {
+            [MathType::Matrix(vec1), MathType::Matrix(vec2)] if vec1.is_vector() && vec2.is_vector() => {
+                let (vec1, vec2) = (vec1.entries(), vec2.entries());
+                match vec1.len() == vec2.len() {
+                    true => Ok(MathType::Number(iter::zip(vec1, vec2).map(|(num1, num2)| *num1 * *num2).sum())),
+                    false => Err(ExecutionError::BadFunctionArgs("dot with unequal vector lengths".to_string())),
+                }
             },
-            _ => Err(ExecutionError::BadFunctionArgs("dot".to_string()))
+            _ => Err(ExecutionError::BadFunctionArgs("dot".to_string())),
         },
-        "cross" => match(args.get(0), args.get(1)) {
-            (Some(MathType::Vector(vec1)), Some(MathType::Vector(vec2))) => match vec1.len() == 3 && vec2.len() == 3 {
-                true => todo!(),
-                false => Err(ExecutionError::BadFunctionArgs("cross with invalid vector lengths".to_string())),
+        "cross" => match args.as_slice() {
+            [MathType::Matrix(vec1), MathType::Matrix(vec2)] if vec1.is_vector() && vec2.is_vector() => {
+                if vec1.entries().len() != 3 || vec2.entries().len() != 3 {
+                    return Err(ExecutionError::BadFunctionArgs("cross with invalid vector lengths".to_string()));
+                }
+                let product = linear_algebra::cross(vec1.entries(), vec2.entries());
+                // the result points the same way as the first vector
+                match vec1.height() {
+                    1 => Ok(MathType::Matrix(Matrix::row_vector(product))),
+                    _ => Ok(MathType::Matrix(Matrix::column_vector(product))),
+                }
             },
             _ => Err(ExecutionError::BadFunctionArgs("cross".to_string())),
         },
-        "rref" => todo!(),
-        "inv" => todo!(),
-        "det" => todo!(),
-        "mean" => todo!(),
-        "median" => todo!(),
+        "transpose" => match args.as_slice() {
+            [value] => value.transpose(),
+            _ => Err(ExecutionError::BadFunctionArgs("transpose".to_string())),
+        },
+        "det" => match args.as_slice() {
+            [matrix] => linear_algebra::det(matrix).map(MathType::Number),
+            _ => Err(ExecutionError::BadFunctionArgs("det".to_string())),
+        },
+        "inv" => match args.as_slice() {
+            [matrix] => linear_algebra::inv(matrix),
+            _ => Err(ExecutionError::BadFunctionArgs("inv".to_string())),
+        },
+        "rref" => match args.as_slice() {
+            [matrix] => linear_algebra::rref(matrix),
+            _ => Err(ExecutionError::BadFunctionArgs("rref".to_string())),
+        },
+        "lu" | "qr" | "chol" | "eig" | "svd" => {
+            let decomposition = match fname {
+                "lu" => linear_algebra::lu,
+                "qr" => linear_algebra::qr,
+                "chol" => linear_algebra::chol,
+                "eig" => linear_algebra::eig,
+                _ => linear_algebra::svd,
+            };
+            match args.as_slice() {
+                [matrix] => decomposition(matrix),
+                _ => Err(ExecutionError::BadFunctionArgs(fname.to_string())),
+            }
+        },
+        "sum" | "prod" | "cumsum" | "mean" | "median" | "mode" | "min" | "max" | "var" | "std" | "quantile" | "percentile" | "cov" | "corr" => {
+            statistics::statistic(fname, &args)
+        },
+        "map" | "filter" | "reduce" | "apply" => higher_order::call(fname, args, environment),
         _ => Err(ExecutionError::UnknownIdentifier(fname.to_string())),
     }
 }
 
+const ELEMENTWISE_OPERATORS: &[&str] = &["+", "-", "//", "%", ".*", "./", ".^", "==", "!=", "<", "<=", ">", ">="];
+
+fn arithmetic(operator: &str) -> fn(Number, Number) -> Number {
+    match operator {
+        "+" => |l, r| l + r,
+        "-" => |l, r| l - r,
+        "*" | ".*" => |l, r| l * r,
+        "/" | "./" => |l, r| l / r,
+        "//" => Number::div_floor,
+        "%" => Number::modulo,
+        _ => Number::pow,
+    }
+}
+
+fn comparison(operator: &str) -> fn(Number, Number) -> bool {
+    match operator {
+        "==" => |l, r| l == r,
+        "!=" => |l, r| l != r,
+        "<" => |l, r| l < r,
+        "<=" => |l, r| l <= r,
+        ">" => |l, r| l > r,
+        _ => |l, r| l >= r,
+    }
+}
+
+fn has_complex(value: &MathType) -> bool {
+    match value {
+        MathType::Number(num) => num.is_complex(),
+        MathType::Matrix(matrix) => matrix.entries().iter().any(|num| num.is_complex()),
+        _ => false,
+    }
+}
+
+// everything but the matrix product goes entry by entry, and a number on either side gets used for every entry
+fn entry_by_entry<T>(lhs: &MathType, operator: &str, rhs: &MathType, function: fn(Number, Number) -> T) -> Result<MathType, ExecutionError>
+where
+    MathType: From<T> + From<Matrix<T>>,
+{
+    match (lhs, rhs) {
+        (MathType::Number(lhs), MathType::Number(rhs)) => Ok(MathType::from(function(*lhs, *rhs))),
+        (MathType::Matrix(lhs), MathType::Number(rhs)) => Ok(MathType::from(lhs.map(|num| function(num, *rhs)))),
+        (MathType::Number(lhs), MathType::Matrix(rhs)) => Ok(MathType::from(rhs.map(|num| function(*lhs, num)))),
+        (MathType::Matrix(l), MathType::Matrix(r)) => l.zip_with(r, function)
+            .map(MathType::from)
+            .ok_or_else(|| match broadcast_shape(l, r) {
+                Some(_) => ExecutionError::InvalidOperation(format!("can't use {operator} on {} and {}, turn on `mode broadcast` to repeat the rows or columns", shape(l), shape(r))),
+                None => ExecutionError::InvalidOperation(format!("can't use {operator} on {} and {}, they need to be the same size", shape(l), shape(r))),
+            }),
+        _ => Err(ExecutionError::InvalidOperation(format!("{} {operator} {}", lhs.type_name(), rhs.type_name()))),
+    }
+}
+
+// each side has to be the same size as the other or 1, in which case that row or column gets repeated
+fn broadcast_shape(lhs: &Matrix, rhs: &Matrix) -> Option<(usize, usize)> {
+    let stretch = |lhs: usize, rhs: usize| match (lhs, rhs) {
+        _ if lhs == rhs => Some(lhs),
+        (1, size) | (size, 1) => Some(size),
+        _ => None,
+    };
+    Some((stretch(lhs.height(), rhs.height())?, stretch(lhs.width(), rhs.width())?))
+}
+
+/// With `mode broadcast on`, stretches two matrices to the same size before going entry by entry, like numpy does.
+/// So a matrix plus a row vector adds it to every row, and a column plus a row gives every pair.
+pub fn broadcast(lhs: MathType, operator: &str, rhs: MathType) -> Result<(MathType, MathType), ExecutionError> {
+    let (MathType::Matrix(l), MathType::Matrix(r)) = (&lhs, &rhs) else {
+        return Ok((lhs, rhs));
+    };
+    if !ELEMENTWISE_OPERATORS.contains(&operator) || (l.height(), l.width()) == (r.height(), r.width()) {
+        return Ok((lhs, rhs));
+    }
+    match broadcast_shape(l, r) {
+        Some((height, width)) => Ok((MathType::Matrix(l.repeat_to(height, width)), MathType::Matrix(r.repeat_to(height, width)))),
+        None => Err(ExecutionError::InvalidOperation(format!("can't broadcast {} and {}, each side has to be the same size or 1", shape(l), shape(r)))),
+    }
+}
+
+// `&&` and `||` only take true and false, going entry by entry for masks
+fn logical(lhs: &MathType, operator: &str, rhs: &MathType) -> Result<MathType, ExecutionError> {
+    let function = |l: bool, r: bool| if operator == "&&" { l && r } else { l || r };
+    match (lhs, rhs) {
+        (MathType::Bool(l), MathType::Bool(r)) => Ok(MathType::Bool(function(*l, *r))),
+        (MathType::Mask(l), MathType::Bool(r)) => Ok(MathType::Mask(l.map(|value| function(value, *r)))),
+        (MathType::Bool(l), MathType::Mask(r)) => Ok(MathType::Mask(r.map(|value| function(*l, value)))),
+        (MathType::Mask(l), MathType::Mask(r)) => l.zip_with(r, function)
+            .map(MathType::Mask)
+            .ok_or_else(|| ExecutionError::InvalidOperation(format!("can't use {operator} on masks that are {} and {}", shape(l), shape(r)))),
+        _ => Err(ExecutionError::InvalidOperation(format!("{operator} only works on true and false, not {} and {}", lhs.type_name(), rhs.type_name()))),
+    }
+}
+
+fn shape<T: Copy>(matrix: &Matrix<T>) -> String {
+    format!("{}x{}", matrix.height(), matrix.width())
+}
+
 impl MathType {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            MathType::Number(_) => "number",
+            MathType::Bool(_) => "bool",
+            MathType::Mask(_) => "mask",
+            MathType::Matrix(matrix) if matrix.height() == 1 => "row vec",
+            MathType::Matrix(matrix) if matrix.width() == 1 => "vec",
+            MathType::Matrix(_) => "matrix",
+            MathType::Quantity(..) => "quantity",
+            MathType::Tuple(_) => "tuple",
+            MathType::Function(_) => "function",
+        }
+    }
+
+    pub fn transpose(&self) -> Result<MathType, ExecutionError> {
+        match self {
+            MathType::Number(_) | MathType::Bool(_) => Ok(self.clone()),
+            MathType::Matrix(matrix) => Ok(MathType::Matrix(matrix.transpose())),
+            MathType::Mask(mask) => Ok(MathType::Mask(mask.transpose())),
+            _ => Err(ExecutionError::InvalidOperation(format!("can't transpose a {}", self.type_name()))),
+        }
+    }
+
+    pub fn not(&self) -> Result<MathType, ExecutionError> {
+        match self {
+            MathType::Bool(value) => Ok(MathType::Bool(!value)),
+            MathType::Mask(mask) => Ok(MathType::Mask(mask.map(|value| !value))),
+            _ => Err(ExecutionError::InvalidOperation(format!("! only works on true and false, not a {}", self.type_name()))),
+        }
+    }
+
+    /// True and false count as 1 and 0 in arithmetic, so `sum(x > 2)` counts how many entries are above 2.
+    pub fn to_numbers(&self) -> MathType {
+        let number = |value: bool| Number::from(if value { 1.0 } else { 0.0 });
+        match self {
+            MathType::Bool(value) => MathType::Number(number(*value)),
+            MathType::Mask(mask) => MathType::Matrix(mask.map(number)),
+            _ => self.clone(),
+        }
+    }
+
     pub fn operate(&self, operator: &str, rhs: MathType) -> Result<MathType, ExecutionError> {
-        match operator {
-            "^" => match self {
-                MathType::Number(lhs) => match rhs {
-                    MathType::Number(rhs) => Ok(MathType::Number(lhs.powf(rhs))),
-                    MathType::Vector(_rhs) => todo!(), // there might be some weird way to do this
-                    MathType::Matrix(_rhs) => todo!(),
-                },
-                MathType::Vector(_lhs) => todo!(),
-                MathType::Matrix(_lhs) => todo!(),
+        match (self, operator, &rhs) {
+            (_, "&&" | "||", _) => logical(self, operator, &rhs),
+            (MathType::Function(_), _, _) | (_, _, MathType::Function(_)) => {
+                Err(ExecutionError::InvalidOperation(format!("can't use {operator} on a function, call it first like f(x)")))
             },
-            "+" => match self {
-                MathType::Number(lhs) => match rhs {
-                    MathType::Number(rhs) => Ok(MathType::Number(lhs + rhs)),
-                    MathType::Vector(_) => Err(ExecutionError::InvalidOperation("number + vec".to_string())),
-                    MathType::Matrix(_) => Err(ExecutionError::InvalidOperation("number + matrix".to_string())),
-                },
-                MathType::Vector(lhs) => match rhs {
-                    MathType::Number(_) => Err(ExecutionError::InvalidOperation("vec + number".to_string())),
-                    MathType::Vector(rhs) => {
-                        if lhs.len() != rhs.len() {
-                            return Err(ExecutionError::InvalidOperation("adding vectors with different size".to_string()))
-                        }
-                        Ok(MathType::Vector(lhs.iter().zip(rhs).map(|(l, r)| l + r).collect()))
-                    },
-                    MathType::Matrix(_rhs) => Err(ExecutionError::InvalidOperation("number + matrix".to_string())),
-                },
-                MathType::Matrix(lhs) => match rhs {
-                    MathType::Number(_) => Err(ExecutionError::InvalidOperation("matrix + number".to_string())),
-                    MathType::Vector(_) => Err(ExecutionError::InvalidOperation("matrix + vec".to_string())),
-                    MathType::Matrix(rhs) => {
-                        let (width, height) = (lhs.len(), lhs.get(0).map(|v| v.len()).unwrap_or(0));
-                        let (width2, height2) = (rhs.len(), rhs.get(0).map(|v| v.len()).unwrap_or(0));
-                        if width != width2 || height != height2 {
-                            return Err(ExecutionError::InvalidOperation("adding matrices with different size".to_string()));
-                        }
-                        Ok(MathType::Matrix(
-                            iter::zip(lhs, rhs).map(|(vec1, vec2)| {
-                                iter::zip(vec1, vec2)
-                                    .map(|(num1, num2)| num1 + num2)
-                                    .collect()
-                            }).collect()
-                        ))
-                    }
-                }
-            },
-            "-" => match self {
-                MathType::Number(lhs) => match rhs {
-                    MathType::Number(rhs) => Ok(MathType::Number(lhs - rhs)),
-                    MathType::Vector(_) => Err(ExecutionError::InvalidOperation("number - vec".to_string())),
-                    MathType::Matrix(_) => Err(ExecutionError::InvalidOperation("number - matrix".to_string())),
-                },
-                MathType::Vector(lhs) => match rhs {
-                    MathType::Number(_) => Err(ExecutionError::InvalidOperation("vec - number".to_string())),
-                    MathType::Vector(rhs) => {
-                        if lhs.len() != rhs.len() {
-                            return Err(ExecutionError::InvalidOperation("subtracting vectors with different size".to_string()))
-                        }
-                        Ok(MathType::Vector(lhs.iter().zip(rhs).map(|(l, r)| l - r).collect()))
-                    },
-                    MathType::Matrix(_rhs) => Err(ExecutionError::InvalidOperation("number / matrix".to_string())),
-                },
-                MathType::Matrix(lhs) => match rhs {
-                    MathType::Number(_) => Err(ExecutionError::InvalidOperation("matrix - number".to_string())),
-                    MathType::Vector(_) => Err(ExecutionError::InvalidOperation("matrix - vec".to_string())),
-                    MathType::Matrix(rhs) => {
-                        let (width, height) = (lhs.len(), lhs.get(0).map(|v| v.len()).unwrap_or(0));
-                        let (width2, height2) = (rhs.len(), rhs.get(0).map(|v| v.len()).unwrap_or(0));
-                        if width != width2 || height != height2 {
-                            return Err(ExecutionError::InvalidOperation("subtracting matrices with different size".to_string()));
-                        }
-                        Ok(MathType::Matrix(
-                            iter::zip(lhs, rhs).map(|(vec1, vec2)| {
-                                iter::zip(vec1, vec2)
-                                    .map(|(num1, num2)| num1 - num2)
-                                    .collect()
-                            }).collect()
-                        ))
-                    },
-                },
-            },
-            "*" => match self {
-                MathType::Number(lhs) => match rhs {
-                    MathType::Number(rhs) => Ok(MathType::Number(lhs * rhs)),
-                    MathType::Vector(rhs) => Ok(MathType::Vector(rhs.iter().map(|v| v * lhs).collect())),
-                    MathType::Matrix(rhs) => Ok(MathType::Matrix(rhs.iter().map(|vec| vec.iter().map(|num| num * lhs).collect()).collect())),
-                },
-                MathType::Vector(lhs) => match rhs {
-                    MathType::Number(rhs) => Ok(MathType::Vector(lhs.iter().map(|v| v * rhs).collect())),
-                    MathType::Vector(_) => Err(ExecutionError::InvalidOperation("vec * vec".to_string())),
-                    MathType::Matrix(_) => Err(ExecutionError::InvalidOperation("vector * matrix".to_string())),
-                },
-                MathType::Matrix(lhs) => match rhs {
-                    MathType::Number(rhs) => Ok(MathType::Matrix(lhs.iter().map(|vec| vec.iter().map(|num| num * rhs).collect()).collect())),
-                    MathType::Vector(rhs) => {
-                        let (width1, height1) = (lhs.len(), lhs.get(0).map(|v| v.len()).unwrap_or(0));
-                        let height2 = rhs.len();
-                        if width1 != height2 {
-                            return Err(ExecutionError::InvalidOperation("matrix width does not match vector height".to_string()));
-                        }
-                        let mut vector = Vec::new();
-                        let mut row = 0;
-                        while row < height1 {
-                            let mut col = 0;
-                            let mut dotsum = 0.0;
-                            while col < width1 {
-                                dotsum += lhs[col][row] * rhs[col];
-                                col += 1;
-                            }
-                            vector.push(dotsum);
-                            row += 1;
-                        }
-                        Ok(MathType::Vector(vector))
-                    },
-                    MathType::Matrix(rhs) => {
-                        let (width1, height1) = (lhs.len(), lhs.get(0).map(|v| v.len()).unwrap_or(0));
-                        let (width2, height2) = (rhs.len(), rhs.get(0).map(|v| v.len()).unwrap_or(0));
-                        if width1 != height2 {
-                            return Err(ExecutionError::InvalidOperation("matrix1 width does not match matrix2 height".to_string()));
-                        }
-                        let mut matrix: Vec<Vec<f64>> = Vec::new();
-                        let mut row1 = 0;
-                        while row1 < height1 {
-                            let mut i = 0;
-                            while i < width2 {
-                                let mut dotsum = 0.0;
-                                let mut col1 = 0;
-                                while col1 < width1 {
-                                    dotsum += lhs[col1][row1] * rhs[i][col1];
-                                    col1 += 1;
-                                }
-                                match matrix.get_mut(i) {
-                                    Some(vector) => vector.push(dotsum),
-                                    None => matrix.push(vec![dotsum]),
-                                }
-                                i += 1;
-                            }
-                            row1 += 1;
-                        }
-                        Ok(MathType::Matrix(matrix))
-                    },
-                },
-            },
-            "/" => match self {
-                    MathType::Number(lhs) => match rhs {
-                        MathType::Number(rhs) => Ok(MathType::Number(lhs / rhs)),
-                        MathType::Vector(_) => Err(ExecutionError::InvalidOperation("number / vec".to_string())),
-                        MathType::Matrix(_) => Err(ExecutionError::InvalidOperation("number / matrix".to_string())),
-                    },
-                    MathType::Vector(lhs) => match rhs {
-                        MathType::Number(rhs) => Ok(MathType::Vector(lhs.iter().map(|v| v / rhs).collect())),
-                        MathType::Vector(_) => Err(ExecutionError::InvalidOperation("vec / vec".to_string())),
-                        MathType::Matrix(_) => Err(ExecutionError::InvalidOperation("vec / matrix".to_string())),
-                    },
-                    MathType::Matrix(lhs) => match rhs {
-                        MathType::Number(rhs) => Ok(MathType::Matrix(lhs.iter().map(|vec| vec.iter().map(|num| num * rhs).collect()).collect())),
-                        MathType::Vector(_rhs) => todo!(),
-                        MathType::Matrix(_rhs) => todo!(),
-                    },
+            (MathType::Bool(_) | MathType::Mask(_), _, _) | (_, _, MathType::Bool(_) | MathType::Mask(_)) => self.to_numbers().operate(operator, rhs.to_numbers()),
+            (MathType::Quantity(..), _, _) | (_, _, MathType::Quantity(..)) => units::operate_quantities(self, operator, &rhs),
+
+            (MathType::Number(lhs), "^", MathType::Number(rhs)) => Ok(MathType::Number(lhs.pow(*rhs))),
+            (MathType::Matrix(lhs), "^", MathType::Number(rhs)) => linear_algebra::power(lhs, *rhs),
+            // there might be some weird way to do these
+            (_, "^", _) => Err(ExecutionError::NotImplemented(format!("{} ^ {}", self.type_name(), rhs.type_name()))),
+
+            // the usual linear algebra rules, so a row times a column is a number and a column times a row is a matrix
+            (MathType::Matrix(lhs), "*", MathType::Matrix(rhs)) => lhs.product(rhs)
+                .map(MathType::from)
+                .ok_or_else(|| ExecutionError::InvalidOperation(format!("can't multiply {} by {}, the left width has to match the right height", shape(lhs), shape(rhs)))),
+            (MathType::Matrix(_), "/", MathType::Matrix(_)) => {
+                Err(ExecutionError::NotImplemented(format!("{} / {}", self.type_name(), rhs.type_name())))
             },
-            "//" => {
-                todo!();
+
+            // `A \ b` solves `A*x = b`, which for a plain number is just dividing the other way round
+            (MathType::Number(_), "\\", _) => rhs.operate("/", self.clone()),
+            (MathType::Matrix(_), "\\", _) => linear_algebra::left_divide(self, &rhs),
+
+            (_, "+" | "-" | "*" | "/" | "//" | "%" | ".*" | "./" | ".^", _) => entry_by_entry(self, operator, &rhs, arithmetic(operator)),
+
+            (_, "<" | "<=" | ">" | ">=", _) if has_complex(self) || has_complex(&rhs) => {
+                Err(ExecutionError::InvalidOperation("complex numbers can't be put in order, try comparing abs(z)".to_string()))
             },
+            (_, "==" | "!=" | "<" | "<=" | ">" | ">=", _) => entry_by_entry(self, operator, &rhs, comparison(operator)),
+
+            (_, "\\", _) => Err(ExecutionError::InvalidOperation(format!("{} {operator} {}", self.type_name(), rhs.type_name()))),
+            (_, "to", _) => Err(ExecutionError::InvalidOperation("can only convert quantities with units".to_string())),
             _ => Err(ExecutionError::UnknownOperator(operator.to_string())),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{column, numbers};
+
+    fn call(fname: &str, args: &[f64], trig_mode: TrigMode) -> f64 {
+        let mut environment = Environment { trig_mode, ..Default::default() };
+        let args = args.iter().map(|num| MathType::Number(Number::from(*num))).collect();
+        match execute_builtin_function(fname, args, &mut environment) {
+            Ok(MathType::Number(num)) => num.to_f64(),
+            other => panic!("expected a number from {fname}, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn trig_uses_angle_mode() {
+        assert_eq!(call("sin", &[90.0], TrigMode::Deg), 1.0);
+        assert_eq!(call("cos", &[180.0], TrigMode::Deg), -1.0);
+        assert_eq!(call("sin", &[-180.0], TrigMode::Deg), 0.0);
+        assert!((call("sin", &[90.0], TrigMode::Rad) - 90.0_f64.sin()).abs() < 1e-12);
+        assert!((call("sec", &[60.0], TrigMode::Deg) - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn inverse_trig_returns_active_unit() {
+        assert_eq!(call("asin", &[1.0], TrigMode::Deg), 90.0);
+        assert_eq!(call("atan2", &[1.0, -1.0], TrigMode::Deg), 135.0);
+        assert!((call("acos", &[0.0], TrigMode::Rad) - std::f64::consts::FRAC_PI_2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn hyperbolic_ignores_angle_mode() {
+        assert_eq!(call("sinh", &[1.0], TrigMode::Deg), call("sinh", &[1.0], TrigMode::Rad));
+        assert!((call("atanh", &[call("tanh", &[0.5], TrigMode::Deg)], TrigMode::Deg) - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn complex_parts() {
+        let mut environment = Environment::default();
+        let z = MathType::Number(Number::complex(-1.0, 1.0));
+        let mut part = |fname: &str| match execute_builtin_function(fname, vec![z.clone()], &mut environment) {
+            Ok(MathType::Number(num)) => num,
+            other => panic!("expected a number from {fname}, got {:?}", other),
+        };
+        assert_eq!(part("re"), Number::from(-1.0));
+        assert_eq!(part("im"), Number::from(1.0));
+        assert_eq!(part("conj"), Number::complex(-1.0, -1.0));
+        assert_eq!(part("arg"), Number::from(135.0));
+        assert!(execute_builtin_function("sin", vec![z.clone()], &mut environment).is_err());
+    }
+
+    #[test]
+    fn vector_shapes() {
+        let row = MathType::Matrix(Matrix::row_vector(numbers(&[1, 2, 3])));
+        let column = column(&[4, 5, 6]);
+        assert!(matches!(row.operate("*", column.clone()), Ok(MathType::Number(num)) if num == Number::from(32)));
+        assert!(matches!(column.operate("*", row.clone()), Ok(MathType::Matrix(outer)) if outer.height() == 3 && outer.width() == 3));
+        assert!(matches!(row.transpose(), Ok(MathType::Matrix(transposed)) if transposed == Matrix::column_vector(numbers(&[1, 2, 3]))));
+        assert!(row.operate("*", row.clone()).is_err());
+        assert!(row.operate("+", column).is_err());
+    }
+
+    #[test]
+    fn elementwise_and_powers() {
+        let row = MathType::Matrix(Matrix::row_vector(numbers(&[1, 2, 3])));
+        assert!(matches!(row.operate(".*", row.clone()), Ok(MathType::Matrix(product)) if product.entries() == numbers(&[1, 4, 9])));
+        assert!(matches!(MathType::Number(Number::from(2)).operate(".^", row.clone()), Ok(MathType::Matrix(powers)) if powers.entries() == numbers(&[2, 4, 8])));
+        assert!(matches!(row.operate("./", column(&[1, 2, 3])), Err(ExecutionError::InvalidOperation(_))));
+        let fibonacci = MathType::Matrix(Matrix::from_rows(&[numbers(&[1, 1]), numbers(&[1, 0])]));
+        assert!(matches!(fibonacci.operate("^", MathType::Number(Number::from(10))), Ok(MathType::Matrix(power)) if power.entries() == numbers(&[89, 55, 55, 34])));
+        assert!(matches!(fibonacci.operate("^", MathType::Number(Number::from(-1))), Ok(MathType::Matrix(inverse)) if inverse.entries() == numbers(&[0, 1, 1, -1])));
+        assert!(row.operate("^", MathType::Number(Number::from(2))).is_err());
+    }
+
+    #[test]
+    fn comparisons_and_logic() {
+        let row = MathType::Matrix(Matrix::row_vector(numbers(&[1, 5, 3])));
+        let mask = row.operate(">", MathType::Number(Number::from(2))).unwrap();
+        assert!(matches!(&mask, MathType::Mask(mask) if mask.entries() == [false, true, true]));
+        assert!(matches!(mask.not(), Ok(MathType::Mask(inverse)) if inverse.entries() == [true, false, false]));
+        assert!(matches!(mask.operate("&&", MathType::Bool(false)), Ok(MathType::Mask(none)) if none.entries() == [false; 3]));
+        assert!(matches!(mask.operate("+", MathType::Number(Number::one())), Ok(MathType::Matrix(sums)) if sums.entries() == numbers(&[1, 2, 2])));
+        assert!(matches!(MathType::Number(Number::ratio(1, 2)).operate("==", MathType::Number(Number::from(0.5))), Ok(MathType::Bool(true))));
+        assert!(MathType::Number(Number::complex(0.0, 1.0)).operate("<", MathType::Number(Number::one())).is_err());
+        assert!(MathType::Number(Number::one()).operate("||", MathType::Bool(true)).is_err());
+    }
+
+    #[test]
+    fn broadcasting() {
+        let row = MathType::Matrix(Matrix::row_vector(numbers(&[1, 2, 4])));
+        assert!(matches!(MathType::Number(Number::from(8)).operate("/", row.clone()), Ok(MathType::Matrix(halves)) if halves.entries() == numbers(&[8, 4, 2])));
+        assert!(matches!(row.operate("-", MathType::Number(Number::one())), Ok(MathType::Matrix(shifted)) if shifted.entries() == numbers(&[0, 1, 3])));
+
+        let column = column(&[10, 20]);
+        assert!(matches!(row.operate("+", column.clone()), Err(ExecutionError::InvalidOperation(_))));
+        let (lhs, rhs) = broadcast(row.clone(), "+", column.clone()).unwrap();
+        assert!(matches!(lhs.operate("+", rhs), Ok(MathType::Matrix(sums)) if sums.entries() == numbers(&[11, 12, 14, 21, 22, 24])));
+        let square = MathType::Matrix(Matrix::identity(2));
+        assert!(matches!(broadcast(square.clone(), "-", row.clone()), Err(ExecutionError::InvalidOperation(_))));
+        assert!(matches!(broadcast(square, "*", column), Ok((MathType::Matrix(lhs), _)) if lhs == Matrix::identity(2)));
+    }
+}
@@ -0,0 +1,230 @@
+use std::cmp::{Ordering, Reverse};
+use std::iter;
+
+use crate::executor::{ExecutionError, MathType};
+use crate::matrix::Matrix;
+use crate::number::Number;
+
+// a number is a list of one, and row and column vectors are both just lists, so is `[]`
+fn as_list(value: &MathType) -> Option<Vec<Number>> {
+    match value {
+        MathType::Number(num) => Some(vec![*num]),
+        MathType::Matrix(matrix) if matrix.is_vector() || matrix.entries().is_empty() => Some(matrix.entries().to_vec()),
+        _ => None,
+    }
+}
+
+// lists give one result, and matrices give a row vector with one for each column like in Matlab
+fn by_column(fname: &str, value: &MathType, reduce: impl Fn(&[Number]) -> Result<Number, ExecutionError>) -> Result<MathType, ExecutionError> {
+    match (as_list(value), value) {
+        (Some(list), _) => reduce(&list).map(MathType::Number),
+        (None, MathType::Matrix(matrix)) => matrix.columns().map(|col| reduce(&col)).collect::<Result<_, _>>().map(|row| MathType::Matrix(Matrix::row_vector(row))),
+        _ => Err(ExecutionError::BadFunctionArgs(format!("{fname}, expected a vector or matrix"))),
+    }
+}
+
+fn non_empty<'a>(fname: &str, values: &'a [Number]) -> Result<&'a [Number], ExecutionError> {
+    match values.is_empty() {
+        true => Err(ExecutionError::BadFunctionArgs(format!("{fname} of an empty vector"))),
+        false => Ok(values),
+    }
+}
+
+// nan has no place in a partial order, so it falls back to sorting after everything else
+fn compare(lhs: &Number, rhs: &Number) -> Ordering {
+    lhs.partial_cmp(rhs).unwrap_or_else(|| lhs.to_f64().total_cmp(&rhs.to_f64()))
+}
+
+fn sorted(fname: &str, values: &[Number]) -> Result<Vec<Number>, ExecutionError> {
+    if values.iter().any(|num| num.is_complex()) {
+        return Err(ExecutionError::BadFunctionArgs(format!("{fname} of a complex number")));
+    }
+    let mut values = non_empty(fname, values)?.to_vec();
+    values.sort_by(compare);
+    Ok(values)
+}
+
+fn mean(values: &[Number]) -> Result<Number, ExecutionError> {
+    let count = non_empty("mean", values)?.len();
+    Ok(values.iter().copied().sum::<Number>() / Number::from(count as i64))
+}
+
+// linear interpolation between the closest ranks, the same as numpy's default
+fn quantile(fname: &str, values: &[Number], fraction: Number) -> Result<Number, ExecutionError> {
+    if fraction.is_complex() || !(Number::zero()..=Number::one()).contains(&fraction) {
+        return Err(ExecutionError::BadFunctionArgs(format!("{fname}, expected a fraction between 0 and 1")));
+    }
+    let values = sorted(fname, values)?;
+    let position = Number::from(values.len() as i64 - 1) * fraction;
+    let below = position.floor();
+    let index = below.as_integer().unwrap_or(0) as usize;
+    match values.get(index + 1) {
+        Some(above) => Ok(values[index] + (*above - values[index]) * (position - below)),
+        None => Ok(values[index]),
+    }
+}
+
+// the most common value, ties go to the smallest one
+fn mode(values: &[Number]) -> Result<Number, ExecutionError> {
+    let values = sorted("mode", values)?;
+    let longest = values.chunk_by(|a, b| a == b).min_by_key(|run| Reverse(run.len()));
+    Ok(longest.expect("sorted values are never empty")[0])
+}
+
+// `sample` divides by n - 1 for an estimate from a sample, otherwise it divides by n
+fn covariance(fname: &str, x: &[Number], y: &[Number], sample: bool) -> Result<Number, ExecutionError> {
+    if x.len() != y.len() {
+        return Err(ExecutionError::BadFunctionArgs(format!("{fname} with unequal vector lengths")));
+    }
+    let (x_mean, y_mean) = (mean(non_empty(fname, x)?)?, mean(y)?);
+    let total: Number = iter::zip(x, y).map(|(x, y)| (*x - x_mean) * (*y - y_mean).conj()).sum();
+    let count = match sample {
+        true => (x.len() as i64 - 1).max(1),
+        false => x.len() as i64,
+    };
+    Ok(total / Number::from(count))
+}
+
+fn variance(fname: &str, values: &[Number], sample: bool) -> Result<Number, ExecutionError> {
+    covariance(fname, values, values, sample).map(Number::re)
+}
+
+fn correlation(x: &[Number], y: &[Number]) -> Result<Number, ExecutionError> {
+    let spread = variance("corr", x, true)? * variance("corr", y, true)?;
+    Ok(covariance("corr", x, y, true)? / spread.map_float(f64::sqrt))
+}
+
+// `cov(x, y)` gives a number, `cov(A)` gives the matrix of every pair of columns
+fn pairwise(fname: &str, args: &[MathType], function: impl Fn(&[Number], &[Number]) -> Result<Number, ExecutionError>) -> Result<MathType, ExecutionError> {
+    match args {
+        [x, y] => match (as_list(x), as_list(y)) {
+            (Some(x), Some(y)) => function(&x, &y).map(MathType::Number),
+            _ => Err(ExecutionError::BadFunctionArgs(format!("{fname}, expected two vectors"))),
+        },
+        [value] => match (as_list(value), value) {
+            (Some(list), _) => function(&list, &list).map(MathType::Number),
+            (None, MathType::Matrix(matrix)) => {
+                let columns: Vec<Vec<Number>> = matrix.columns().collect();
+                let rows = columns.iter()
+                    .map(|x| columns.iter().map(|y| function(x, y)).collect())
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(MathType::Matrix(Matrix::from_rows(&rows)))
+            },
+            _ => Err(ExecutionError::BadFunctionArgs(format!("{fname}, expected a vector or matrix"))),
+        },
+        _ => Err(ExecutionError::BadFunctionArgs(fname.to_string())),
+    }
+}
+
+fn running_sum(values: &[Number]) -> Vec<Number> {
+    values.iter().scan(Number::zero(), |total, num| {
+        *total = *total + *num;
+        Some(*total)
+    }).collect()
+}
+
+// population statistics are asked for with a second argument of 1, like `std(x, 1)` in Matlab
+fn is_population(fname: &str, args: &[MathType]) -> Result<bool, ExecutionError> {
+    match args.get(1) {
+        None => Ok(false),
+        Some(MathType::Number(flag)) if flag.is_zero() || *flag == Number::one() => Ok(*flag == Number::one()),
+        Some(_) => Err(ExecutionError::BadFunctionArgs(format!("{fname}, expected 0 for a sample or 1 for a population"))),
+    }
+}
+
+/// Builtins that summarize a vector, or each column of a matrix.
+pub fn statistic(fname: &str, args: &[MathType]) -> Result<MathType, ExecutionError> {
+    let args: Vec<MathType> = args.iter().map(MathType::to_numbers).collect();
+    let args = args.as_slice();
+    let value = match args {
+        [] => return Err(ExecutionError::BadFunctionArgs(fname.to_string())),
+        [value, ..] => value,
+    };
+    match (fname, args) {
+        ("sum", [_]) => by_column(fname, value, |values| Ok(values.iter().copied().sum())),
+        ("prod", [_]) => by_column(fname, value, |values| Ok(values.iter().fold(Number::one(), |product, num| product * *num))),
+        ("mean", [_]) => by_column(fname, value, mean),
+        ("median", [_]) => by_column(fname, value, |values| quantile(fname, values, Number::ratio(1, 2))),
+        ("mode", [_]) => by_column(fname, value, mode),
+        ("min", [_]) => by_column(fname, value, |values| Ok(sorted(fname, values)?[0])),
+        ("max", [_]) => by_column(fname, value, |values| Ok(*sorted(fname, values)?.last().expect("sorted values are never empty"))),
+        ("min" | "max", [MathType::Number(a), MathType::Number(b)]) => {
+            let values = sorted(fname, &[*a, *b])?;
+            Ok(MathType::Number(if fname == "min" { values[0] } else { values[1] }))
+        },
+        ("var", [_] | [_, _]) => {
+            let sample = !is_population(fname, args)?;
+            by_column(fname, value, |values| variance(fname, values, sample))
+        },
+        ("std", [_] | [_, _]) => {
+            let sample = !is_population(fname, args)?;
+            by_column(fname, value, |values| Ok(variance(fname, values, sample)?.map_float(f64::sqrt)))
+        },
+        ("quantile" | "percentile", [_, MathType::Number(amount)]) => {
+            let fraction = if fname == "percentile" { *amount / Number::from(100) } else { *amount };
+            by_column(fname, value, |values| quantile(fname, values, fraction))
+        },
+        ("cumsum", [_]) => match value {
+            MathType::Number(_) => Ok(value.clone()),
+            // a vector keeps its shape, since the running sum of a row is the same as along its only column
+            MathType::Matrix(matrix) if matrix.height() == 1 => Ok(MathType::Matrix(Matrix::row_vector(running_sum(matrix.entries())))),
+            MathType::Matrix(matrix) => Ok(MathType::Matrix(Matrix::from_columns(&matrix.columns().map(|col| running_sum(&col)).collect::<Vec<_>>()))),
+            _ => Err(ExecutionError::BadFunctionArgs(format!("{fname}, expected a vector or matrix"))),
+        },
+        ("cov", _) => pairwise(fname, args, |x, y| covariance(fname, x, y, true)),
+        ("corr", _) => pairwise(fname, args, correlation),
+        _ => Err(ExecutionError::BadFunctionArgs(fname.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::column;
+
+    fn number(fname: &str, args: &[MathType]) -> Number {
+        match statistic(fname, args) {
+            Ok(MathType::Number(num)) => num,
+            other => panic!("expected a number from {fname}, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn averages_stay_exact() {
+        let values = &[column(&[3, 1, 4, 1, 5, 9])];
+        assert_eq!(number("mean", values), Number::ratio(23, 6));
+        assert_eq!(number("median", values), Number::ratio(7, 2));
+        assert_eq!(number("mode", values), Number::from(1));
+        assert_eq!(number("sum", values), Number::from(23));
+        assert_eq!(number("prod", values), Number::from(540));
+        assert_eq!(number("max", values), Number::from(9));
+        assert_eq!(number("percentile", &[values[0].clone(), MathType::Number(Number::from(25))]), Number::ratio(3, 2));
+    }
+
+    #[test]
+    fn spread_and_correlation() {
+        let values = column(&[2, 4, 4, 4, 5, 5, 7, 9]);
+        assert_eq!(number("var", &[values.clone(), MathType::Number(Number::one())]), Number::from(4));
+        assert_eq!(number("var", std::slice::from_ref(&values)), Number::ratio(32, 7));
+        assert_eq!(number("std", &[values, MathType::Number(Number::one())]), Number::from(2.0));
+        assert_eq!(number("cov", &[column(&[1, 2, 3]), column(&[2, 4, 6])]), Number::from(2));
+        assert_eq!(number("corr", &[column(&[1, 2, 3]), column(&[3, 2, 1])]), Number::from(-1.0));
+    }
+
+    #[test]
+    fn matrices_work_by_column() {
+        let matrix = MathType::Matrix(Matrix::from_rows(&[vec![Number::from(1), Number::from(2)], vec![Number::from(3), Number::from(6)]]));
+        assert!(matches!(statistic("mean", std::slice::from_ref(&matrix)), Ok(MathType::Matrix(means)) if means == Matrix::row_vector(vec![Number::from(2), Number::from(4)])));
+        assert!(matches!(statistic("cumsum", &[matrix]), Ok(MathType::Matrix(sums)) if sums.column(1) == [Number::from(2), Number::from(8)]));
+        let row = MathType::Matrix(Matrix::from_rows(&[vec![Number::from(1), Number::from(2)]]));
+        assert_eq!(number("sum", &[row]), Number::from(3));
+    }
+
+    #[test]
+    fn empty_and_complex_are_errors() {
+        assert!(matches!(statistic("mean", &[column(&[])]), Err(ExecutionError::BadFunctionArgs(_))));
+        assert!(matches!(statistic("median", &[column(&[])]), Err(ExecutionError::BadFunctionArgs(_))));
+        assert!(matches!(statistic("max", &[MathType::Matrix(Matrix::row_vector(vec![Number::complex(1.0, 1.0)]))]), Err(ExecutionError::BadFunctionArgs(_))));
+        assert!(matches!(statistic("quantile", &[column(&[1]), MathType::Number(Number::from(2))]), Err(ExecutionError::BadFunctionArgs(_))));
+    }
+}
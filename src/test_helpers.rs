@@ -0,0 +1,28 @@
+// shorthand for building values in the unit tests, written the way they'd be typed
+
+use crate::executor::MathType;
+use crate::matrix::Matrix;
+use crate::number::Number;
+use crate::units::Unit;
+
+pub fn numbers(values: &[i64]) -> Vec<Number> {
+    values.iter().map(|num| Number::from(*num)).collect()
+}
+
+// gives either a Matrix or a MathType, whichever the test needs
+pub fn matrix<T: From<Matrix>>(rows: &[&[i64]]) -> T {
+    let rows: Vec<Vec<Number>> = rows.iter().map(|row| numbers(row)).collect();
+    T::from(Matrix::from_rows(&rows))
+}
+
+pub fn column(values: &[i64]) -> MathType {
+    MathType::Matrix(Matrix::column_vector(numbers(values)))
+}
+
+pub fn number(num: f64) -> MathType {
+    MathType::Number(Number::from(num))
+}
+
+pub fn unit(symbol: &str) -> MathType {
+    MathType::Quantity(Number::one(), Unit::parse(symbol).unwrap())
+}
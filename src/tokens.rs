@@ -1,50 +1,312 @@
-#[allow(dead_code)]
+use std::error::Error;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use crate::number::Number;
+
+// longer operators need to come first so they get matched before their prefixes
+const OPERATORS: &[&str] = &[
+    ".*", "./", ".^", "==", "!=", "<=", ">=", "&&", "||", "=>", "->", "//",
+    "^", "*", "/", "\\", "%", "+", "-", "=", "<", ">", "!", "'",
+];
+// commands like `def` and `q` are plain identifiers here, parse_commands picks them out at the start of a line
+const KEYWORDS: &[&str] = &["if", "then", "else"];
+
+/// Byte range of a token within the input line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+    /// Builds a line of carets that lines up with the span when printed under `line`.
+    pub fn underline(&self, line: &str) -> String {
+        let start = self.start.min(line.len());
+        let end = self.end.clamp(start, line.len());
+        let offset = line.get(..start).map(|s| s.chars().count()).unwrap_or(0);
+        let width = line.get(start..end).map(|s| s.chars().count()).unwrap_or(0).max(1);
+        format!("{}{}", " ".repeat(offset), "^".repeat(width))
+    }
+}
+
 #[derive(Debug)]
 pub enum TokenizeError {
-    IncompleteOperator(String),
+    UnknownCharacter(char, Span),
+    InvalidNumber(String, Span),
 }
 
-pub type Token = String;
+impl TokenizeError {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            TokenizeError::UnknownCharacter(_, span) => Some(*span),
+            TokenizeError::InvalidNumber(_, span) => Some(*span),
+        }
+    }
+}
 
-fn push_token(sequence: &mut Vec<Token>, token: &mut Token) {
-    if token != "" {
-        sequence.push(token.clone());
-        token.clear();
+impl fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenizeError::UnknownCharacter(c, _) => write!(f, "unexpected character '{c}'"),
+            TokenizeError::InvalidNumber(e, _) => write!(f, "invalid number '{e}'"),
+        }
     }
 }
 
-fn is_new_token(last_char: char, new_char: char) -> bool {
-    let is_word = |c: char| c.is_alphanumeric() || c == '.';
-    let is_operator = |c: char| !c.is_whitespace() && c != '.' && !c.is_alphanumeric();
+impl Error for TokenizeError {}
 
-    match last_char {'(' | ')' | '[' | ']' => return true, _=>()};
-    match new_char {'(' | ')' | '[' | ']' => return true, _=>()};
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Number(Number),
+    Identifier(String),
+    Operator(&'static str),
+    Bracket(char),
+    Separator(char),
+    Keyword(&'static str),
+}
 
-    if is_word(last_char) && is_word(new_char) {
-        return false;
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Number(number) => write!(f, "{number}"),
+            TokenKind::Identifier(name) => write!(f, "{name}"),
+            TokenKind::Operator(op) => write!(f, "{op}"),
+            TokenKind::Bracket(c) | TokenKind::Separator(c) => write!(f, "{c}"),
+            TokenKind::Keyword(word) => write!(f, "{word}"),
+        }
     }
-    if is_operator(last_char) && is_operator(new_char) {
-        return false;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+impl Token {
+    pub fn new(kind: TokenKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+    pub fn as_operator(&self) -> Option<&'static str> {
+        match self.kind {
+            TokenKind::Operator(op) => Some(op),
+            _ => None,
+        }
+    }
+    pub fn as_identifier(&self) -> Option<&String> {
+        match &self.kind {
+            TokenKind::Identifier(name) => Some(name),
+            _ => None,
+        }
+    }
+    pub fn as_keyword(&self) -> Option<&'static str> {
+        match self.kind {
+            TokenKind::Keyword(word) => Some(word),
+            _ => None,
+        }
+    }
+    pub fn is_bracket(&self, c: char) -> bool {
+        self.kind == TokenKind::Bracket(c)
+    }
+    pub fn is_separator(&self, c: char) -> bool {
+        self.kind == TokenKind::Separator(c)
     }
-    return true;
 }
 
-pub fn generate_tokens(text: &String) -> Vec<Token> {
-    let mut token_sequence: Vec<Token> = Vec::new();
-    let mut current_token = String::new();
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
 
-    let mut last_c = '_';
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '.'
+}
+
+// a `.` right before `*`, `/` or `^` is the start of an element-wise operator rather than part of a word,
+// so `x.^2` and `2.*x` split the same way Matlab does
+fn starts_elementwise(chars: &Peekable<CharIndices>) -> bool {
+    let mut lookahead = chars.clone();
+    lookahead.next_if(|&(_, c)| c == '.').is_some() && lookahead.peek().is_some_and(|&(_, c)| matches!(c, '*' | '/' | '^'))
+}
 
-    for c in text.chars() {
-        if is_new_token(last_c, c) {
-            push_token(&mut token_sequence, &mut current_token);
+fn is_numeric_start(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+}
+
+// true for things like "6.022e" where a signed exponent can still follow
+fn expects_exponent(word: &str) -> bool {
+    match word.strip_suffix(['e', 'E']) {
+        Some(mantissa) => is_numeric_start(mantissa) && mantissa.parse::<Number>().is_ok(),
+        None => false,
+    }
+}
+
+fn read_word(start: usize, chars: &mut Peekable<CharIndices>) -> (String, Span) {
+    let mut word = String::new();
+    let mut end = start;
+    while let Some(&(i, c)) = chars.peek() {
+        let is_exponent_sign = (c == '+' || c == '-') && expects_exponent(&word) && {
+            let mut lookahead = chars.clone();
+            lookahead.next();
+            lookahead.peek().is_some_and(|(_, next)| next.is_ascii_digit())
+        };
+        if (!is_word(c) && !is_exponent_sign) || starts_elementwise(chars) {
+            break;
+        }
+        word.push(c);
+        end = i + c.len_utf8();
+        chars.next();
+    }
+    (word, Span::new(start, end))
+}
+
+/// Splits a word like `2pi` or `6.022e23mol` into its leading number and the byte length of that number.
+/// Returns None if the word doesn't start with a number or the rest isn't a plain identifier.
+pub fn split_coefficient(word: &str) -> Option<(Number, usize)> {
+    if !is_numeric_start(word) {
+        return None;
+    }
+    word.char_indices()
+        .rev()
+        .map(|(i, _)| i)
+        .filter(|i| *i > 0)
+        .find_map(|i| {
+            let rest = &word[i..];
+            let is_identifier = rest.starts_with(char::is_alphabetic) && rest.chars().all(char::is_alphanumeric);
+            match is_identifier {
+                true => word[..i].parse::<Number>().ok().map(|number| (number, i)),
+                false => None,
+            }
+        })
+}
+
+fn classify_word(word: String, span: Span) -> Result<Vec<Token>, TokenizeError> {
+    if is_numeric_start(&word) {
+        if let Ok(number) = word.parse::<Number>() {
+            return Ok(vec![Token::new(TokenKind::Number(number), span)]);
+        }
+    }
+    match word.as_str() {
+        "inf" => return Ok(vec![Token::new(TokenKind::Number(Number::INFINITY), span)]),
+        "nan" => return Ok(vec![Token::new(TokenKind::Number(Number::NAN), span)]),
+        "to" => return Ok(vec![Token::new(TokenKind::Operator("to"), span)]), // unit conversion, `3 ft to m`
+        _ => {},
+    }
+    if word.contains(['.', '+', '-']) {
+        // something like `2.5x` can't be a variable name, so it's always a coefficient and an identifier
+        return match split_coefficient(&word) {
+            Some((number, len)) => Ok(vec![
+                Token::new(TokenKind::Number(number), Span::new(span.start, span.start + len)),
+                Token::new(TokenKind::Identifier(word[len..].to_string()), Span::new(span.start + len, span.end)),
+            ]),
+            None => Err(TokenizeError::InvalidNumber(word, span)),
+        };
+    }
+    let kind = match KEYWORDS.iter().find(|keyword| **keyword == word) {
+        Some(keyword) => TokenKind::Keyword(keyword),
+        None => TokenKind::Identifier(word),
+    };
+    Ok(vec![Token::new(kind, span)])
+}
+
+fn read_operator(text: &str, start: usize) -> Option<&'static str> {
+    OPERATORS.iter().copied().find(|op| text[start..].starts_with(op))
+}
+
+pub fn generate_tokens(text: &str) -> Result<Vec<Token>, TokenizeError> {
+    let mut token_sequence: Vec<Token> = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some(&(i, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
         }
-        if !c.is_whitespace() {
-            current_token.push(c);
+        if c == '#' {
+            // comment, skip to the end of the line
+            while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+            continue;
         }
-        last_c = c;
+
+        let kind = match c {
+            '(' | ')' | '[' | ']' => {
+                chars.next();
+                TokenKind::Bracket(c)
+            },
+            ',' | ';' => {
+                chars.next();
+                TokenKind::Separator(c)
+            },
+            c if is_word(c) && !starts_elementwise(&chars) => {
+                let (word, span) = read_word(i, &mut chars);
+                token_sequence.extend(classify_word(word, span)?);
+                continue;
+            },
+            _ => match read_operator(text, i) {
+                Some(op) => {
+                    for _ in 0..op.chars().count() {
+                        chars.next();
+                    }
+                    token_sequence.push(Token::new(TokenKind::Operator(op), Span::new(i, i + op.len())));
+                    continue;
+                },
+                None => return Err(TokenizeError::UnknownCharacter(c, Span::new(i, i + c.len_utf8()))),
+            },
+        };
+        token_sequence.push(Token::new(kind, Span::new(i, i + c.len_utf8())));
     }
 
-    push_token(&mut token_sequence, &mut current_token);
-    token_sequence
+    Ok(token_sequence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(text: &str) -> Vec<TokenKind> {
+        generate_tokens(text).unwrap().into_iter().map(|token| token.kind).collect()
+    }
+
+    #[test]
+    fn comments_are_stripped() {
+        assert_eq!(kinds("5 + 5 # simple expression"), kinds("5 + 5"));
+        assert_eq!(kinds("# nothing but a comment"), vec![]);
+        assert_eq!(kinds("def f(x) x # body"), kinds("def f(x) x"));
+        assert_eq!(kinds("[1 2 # first row\n 3 4]"), kinds("[1 2 3 4]"));
+    }
+
+    #[test]
+    fn number_literals() {
+        assert_eq!(kinds("6.022e23"), vec![TokenKind::Number(Number::from(6.022e23))]);
+        assert_eq!(kinds("1.6e-19"), vec![TokenKind::Number(Number::from(1.6e-19))]);
+        assert_eq!(kinds("1e+3"), vec![TokenKind::Number(Number::from(1000))]);
+        assert_eq!(kinds("inf"), vec![TokenKind::Number(Number::INFINITY)]);
+        assert_eq!(kinds("1e+"), vec![TokenKind::Identifier("1e".to_string()), TokenKind::Operator("+")]);
+        assert!(matches!(generate_tokens("1.2.3"), Err(TokenizeError::InvalidNumber(_, _))));
+    }
+
+    #[test]
+    fn elementwise_operators() {
+        assert_eq!(kinds("x.^2"), vec![TokenKind::Identifier("x".to_string()), TokenKind::Operator(".^"), TokenKind::Number(Number::from(2))]);
+        assert_eq!(kinds("2.*x"), vec![TokenKind::Number(Number::from(2)), TokenKind::Operator(".*"), TokenKind::Identifier("x".to_string())]);
+        assert_eq!(kinds("a ./ .5"), vec![TokenKind::Identifier("a".to_string()), TokenKind::Operator("./"), TokenKind::Number(Number::from(0.5))]);
+        assert_eq!(kinds("1./2"), kinds("1 ./ 2"));
+    }
+
+    #[test]
+    fn spans_point_at_source() {
+        let tokens = generate_tokens("ab => 12").unwrap();
+        let spans: Vec<Span> = tokens.iter().map(|token| token.span).collect();
+        assert_eq!(spans, vec![Span::new(0, 2), Span::new(3, 5), Span::new(6, 8)]);
+        assert!(matches!(generate_tokens("1 $ 2"), Err(TokenizeError::UnknownCharacter('$', span)) if span == Span::new(2, 3)));
+    }
 }
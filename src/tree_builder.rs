@@ -1,34 +1,83 @@
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
+use std::sync::Arc;
 
 use crate::{
-    tokens::Token,
-    executor::{Environment, TrigMode},
+    display::{ComplexDisplay, DisplayMode},
+    tokens::{self, Span, Token, TokenKind},
+    executor::{Environment, Function, TrigMode},
+    number::Number,
 };
 
-const UNARY_OPERATORS: &'static [&'static str] = &["-", "&", "!"];
-const BINARY_OPERATOR_PRIORITY: &'static [&'static [&'static str]] = &[
-    &["^"],
-    &["*", "/", "//"],
+const UNARY_OPERATORS: &[&str] = &["-", "!"];
+const POSTFIX_OPERATORS: &[&str] = &["'"];
+const BINARY_OPERATOR_PRIORITY: &[&[&str]] = &[
+    &["^", ".^"],
+    &["*", "/", "\\", "//", "%", ".*", "./"],
     &["+", "-"],
+    &["to"],
+    &["==", "!=", "<", "<=", ">", ">="],
+    &["&&"],
+    &["||"],
     &["=>", "="],
 ];
+const RIGHT_ASSOCIATIVE_OPERATORS: &[&str] = &["^", ".^", "="];
+
+// binding powers that sit between the levels of BINARY_OPERATOR_PRIORITY, see binary_binding_power
+const IMPLIED_PRODUCT_BINDING_POWER: u8 = 15; // tighter than `*`, looser than `^`
+const UNARY_BINDING_POWER: u8 = 15; // `-2^2` is `-(2^2)` and `!a == b` is `(!a) == b`
+const CALL_ARGUMENT_BINDING_POWER: u8 = 17; // `sin x^2` is `(sin x)^2`
+const POSTFIX_BINDING_POWER: u8 = 18; // tighter than anything, so `-A'` is `-(A')` and `sin x'` is `sin(x')`
+
+// parsing and executing both recurse once per level, so this keeps absurd input from overflowing the stack
+const MAX_EXPRESSION_DEPTH: usize = 256;
+// a flat `1 + 1 + 1` doesn't recurse while parsing, but the tree still gets one level deeper for every operator
+const MAX_OPERATORS: usize = 10_000;
+// the executor separately limits how many expressions nest across calls to keep within the stack in main.rs,
+// so this just keeps `mode depth` to something a simple recursive function can actually reach
+const MAX_CALL_DEPTH: usize = 2_000;
 
 #[derive(Debug)]
 pub enum ExpressionBuildError {
-    HangingBrace(String),
+    HangingBrace(String, Span),
     InvalidMode(String),
     InvalidFunctionDefinition(String),
-    HangingOperator(String),
+    HangingOperator(String, Span),
+    UnexpectedToken(String, Span),
+    InvalidAssignment(Span),
+    TooDeeplyNested(Span),
+    TooLong(Span),
+    MissingKeyword(&'static str, Span),
+}
+
+impl ExpressionBuildError {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ExpressionBuildError::HangingBrace(_, span) => Some(*span),
+            ExpressionBuildError::HangingOperator(_, span) => Some(*span),
+            ExpressionBuildError::UnexpectedToken(_, span) => Some(*span),
+            ExpressionBuildError::InvalidAssignment(span) => Some(*span),
+            ExpressionBuildError::TooDeeplyNested(span) => Some(*span),
+            ExpressionBuildError::TooLong(span) => Some(*span),
+            ExpressionBuildError::MissingKeyword(_, span) => Some(*span),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for ExpressionBuildError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ExpressionBuildError::HangingBrace(e) => write!(f, "missing closing {}", e),
+            ExpressionBuildError::HangingBrace(e, _) => write!(f, "missing closing {}", e),
             ExpressionBuildError::InvalidMode(e) => write!(f, "mode update error: {e}"),
             ExpressionBuildError::InvalidFunctionDefinition(e) => write!(f, "invalid function definition: {e}"),
-            ExpressionBuildError::HangingOperator(e) => write!(f, "not enough arguments for operator '{e}'"),
+            ExpressionBuildError::HangingOperator(e, _) => write!(f, "not enough arguments for operator '{e}'"),
+            ExpressionBuildError::UnexpectedToken(e, _) => write!(f, "unexpected '{e}'"),
+            ExpressionBuildError::InvalidAssignment(_) => write!(f, "can only assign to a variable name"),
+            ExpressionBuildError::TooDeeplyNested(_) => write!(f, "expression is nested too deeply"),
+            ExpressionBuildError::TooLong(_) => write!(f, "expression has too many operators"),
+            ExpressionBuildError::MissingKeyword(e, _) => write!(f, "missing '{e}' after 'if'"),
         }
     }
 }
@@ -36,334 +85,820 @@ impl fmt::Display for ExpressionBuildError {
 impl Error for ExpressionBuildError {}
 
 #[derive(Debug, Clone)]
-pub enum Node {
-    Tkn(Token),
-    Exp(Vec<Node>),
+pub enum ExprKind {
+    Number(Number),
+    Ident(String),
+    Call { name: String, args: Vec<Expr>, parens: bool }, // no parens for `sin x` or `kg m`
+    Unary { op: &'static str, operand: Box<Expr> },
+    Postfix { op: &'static str, operand: Box<Expr> }, // transpose, `A'`
+    Binary { op: &'static str, lhs: Box<Expr>, rhs: Box<Expr> },
+    Matrix { rows: Vec<Vec<Expr>> },
+    Assign { name: String, value: Box<Expr> },
+    Destructure { names: Vec<String>, value: Box<Expr> }, // `[L, U, P] = lu(A)`
+    Conditional { condition: Box<Expr>, then: Box<Expr>, otherwise: Box<Expr> }, // `if x < 0 then -x else x`
+    Lambda { params: Vec<String>, body: Arc<Expr> }, // `x -> x^2` or `(x, y) -> x + y`
 }
 
-impl Node {
-    pub fn is_operator(&self) -> bool {
-        self.is_unary_operator() || self.is_binary_operator()
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
     }
-    pub fn is_unary_operator(&self) -> bool {
-        match self {
-            Node::Exp(_) => false,
-            Node::Tkn(token) => {
-                UNARY_OPERATORS.contains(&token.as_str())
-            }
+}
+
+// prints the fully parenthesised form, which is handy for checking how something was parsed
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |exprs: &[Expr]| exprs.iter().map(|expr| expr.to_string()).collect::<Vec<_>>().join(", ");
+        match &self.kind {
+            ExprKind::Number(number) => write!(f, "{number}"),
+            ExprKind::Ident(name) => write!(f, "{name}"),
+            ExprKind::Call { name, args, .. } => write!(f, "{name}({})", join(args)),
+            ExprKind::Unary { op, operand } => write!(f, "({op}{operand})"),
+            ExprKind::Postfix { op, operand } => write!(f, "({operand}{op})"),
+            ExprKind::Binary { op, lhs, rhs } => write!(f, "({lhs} {op} {rhs})"),
+            ExprKind::Matrix { rows } => write!(f, "[{}]", rows.iter().map(|row| join(row)).collect::<Vec<_>>().join("; ")),
+            ExprKind::Assign { name, value } => write!(f, "({name} = {value})"),
+            ExprKind::Destructure { names, value } => write!(f, "([{}] = {value})", names.join(", ")),
+            ExprKind::Conditional { condition, then, otherwise } => write!(f, "(if {condition} then {then} else {otherwise})"),
+            ExprKind::Lambda { params, body } => write!(f, "(({}) -> {body})", params.join(", ")),
         }
     }
-    pub fn is_binary_operator(&self) -> bool {
-        match self {
-            Node::Exp(_) => false,
-            Node::Tkn(token) => BINARY_OPERATOR_PRIORITY.iter().any(|prio| prio.contains(&token.as_str())),
+}
+
+// --------------------------------------------------------------------------------------------------------------------
+
+/// (left, right) binding powers, derived from the position in BINARY_OPERATOR_PRIORITY.
+fn binary_binding_power(op: &str) -> Option<(u8, u8)> {
+    let index = BINARY_OPERATOR_PRIORITY.iter().position(|ops| ops.contains(&op))?;
+    let left = ((BINARY_OPERATOR_PRIORITY.len() - index) * 2) as u8;
+    match RIGHT_ASSOCIATIVE_OPERATORS.contains(&op) {
+        true => Some((left, left)),
+        false => Some((left, left + 1)),
+    }
+}
+
+fn starts_operand(token: &Token) -> bool {
+    matches!(token.kind, TokenKind::Number(_) | TokenKind::Identifier(_) | TokenKind::Bracket('(') | TokenKind::Bracket('['))
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    implicit_multiplication: bool,
+    // inside `[...]` whitespace separates elements, so `[1 -2]` has two elements and `[f (x)]` isn't a call
+    in_matrix: bool,
+    depth: usize,
+    operators: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(tokens: &'a [Token], implicit_multiplication: bool) -> Self {
+        Self { tokens, position: 0, implicit_multiplication, in_matrix: false, depth: 0, operators: 0 }
+    }
+
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn advance(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    fn previous_span(&self) -> Span {
+        match self.position.checked_sub(1).and_then(|i| self.tokens.get(i)) {
+            Some(token) => token.span,
+            None => Span::new(0, 0),
         }
     }
-    pub fn is_str(&self, s: &str) -> bool {
-        match self {
-            Node::Exp(_) => false,
-            Node::Tkn(token) => token == s,
+
+    // whether the next token is written directly against the previous one, with no whitespace between
+    fn next_is_adjacent(&self) -> bool {
+        self.peek().is_some_and(|token| token.span.start == self.previous_span().end)
+    }
+
+    // whether the next token can be glued onto the previous operand as a call argument or implied factor
+    fn next_continues_operand(&self) -> bool {
+        self.peek().is_some_and(starts_operand) && (!self.in_matrix || self.next_is_adjacent())
+    }
+
+    // a coefficient only goes in front of a name or a bracket, two numbers in a row like `2 3` are more likely a missing operator
+    fn next_continues_coefficient(&self) -> bool {
+        self.next_continues_operand() && !self.peek().is_some_and(|token| matches!(token.kind, TokenKind::Number(_)))
+    }
+
+    // `[1 -2]`: a sign with whitespace before it but not after starts a new matrix element
+    fn next_starts_matrix_element(&self) -> bool {
+        if !self.in_matrix {
+            return false;
         }
+        let sign = match self.peek() {
+            Some(token) if matches!(token.as_operator(), Some("-" | "+")) => token,
+            _ => return false,
+        };
+        let spaced_before = sign.span.start != self.previous_span().end;
+        let spaced_after = self.tokens.get(self.position + 1).is_none_or(|next| next.span.start != sign.span.end);
+        spaced_before && !spaced_after
     }
-    // other is_..._operator functions should also return option rather than bool, but idc rn
-    pub fn as_assignment_operator(&self) -> Option<&String> {
-        match self {
-            Node::Exp(_) => None,
-            Node::Tkn(token) => match token.as_str() {
-                "=" | "=>" => Some(token),
-                _ => None,
+
+    fn expect_closing(&mut self, closing: char, opening: &Token) -> Result<Span, ExpressionBuildError> {
+        match self.peek() {
+            Some(token) if token.is_bracket(closing) => {
+                self.advance();
+                Ok(token.span)
             },
+            Some(token) => Err(ExpressionBuildError::UnexpectedToken(token.to_string(), token.span)),
+            None => Err(ExpressionBuildError::HangingBrace(closing.to_string(), opening.span)),
         }
     }
-    pub fn as_identifier(&self) -> Option<&String> {
-        match self {
-            Node::Exp(_) => None,
-            Node::Tkn(token) => Some(token),
+
+    fn expect_keyword(&mut self, keyword: &'static str, opening: &Token) -> Result<(), ExpressionBuildError> {
+        match self.peek() {
+            Some(token) if token.as_keyword() == Some(keyword) => {
+                self.advance();
+                Ok(())
+            },
+            Some(token) => Err(ExpressionBuildError::UnexpectedToken(token.to_string(), token.span)),
+            None => Err(ExpressionBuildError::MissingKeyword(keyword, opening.span)),
         }
     }
-    pub fn unrolled(&self) -> &Node {
-        match self {
-            Node::Exp(subnodes) => match subnodes.len() {
-                1 => subnodes[0].unrolled(),
-                _ => self,
-            },
-            Node::Tkn(_) => self
+
+    // counts a level of nesting in the tree, nothing is restored on error since parsing stops anyway
+    fn descend(&mut self) -> Result<(), ExpressionBuildError> {
+        self.depth += 1;
+        match self.depth > MAX_EXPRESSION_DEPTH {
+            true => Err(ExpressionBuildError::TooDeeplyNested(self.previous_span())),
+            false => Ok(()),
         }
     }
-    pub fn flat_string(&self) -> String {
-        let mut res = String::new();
-        match self {
-            Node::Tkn(token) => res += &format!("'{}', ", token),
-            Node::Exp(subnodes) => subnodes.iter().for_each(|node| res += &node.flat_string()),
+
+    fn count_operator(&mut self, span: Span) -> Result<(), ExpressionBuildError> {
+        self.operators += 1;
+        match self.operators > MAX_OPERATORS {
+            true => Err(ExpressionBuildError::TooLong(span)),
+            false => Ok(()),
         }
-        res
     }
-    fn pretty_string(&self, depth: usize) -> String {
-        let mut res = String::new();
 
-        for _ in 0..depth {
-            res.push_str("\t");
+    fn parse_expression(&mut self, min_binding_power: u8) -> Result<Expr, ExpressionBuildError> {
+        self.descend()?;
+        let lhs = self.parse_prefix()?;
+        let expression = self.parse_infix(lhs, min_binding_power)?;
+        self.depth -= 1;
+        Ok(expression)
+    }
+
+    fn parse_infix(&mut self, mut lhs: Expr, min_binding_power: u8) -> Result<Expr, ExpressionBuildError> {
+        while let Some(token) = self.peek() {
+            let op = match token.as_operator() {
+                Some(op) => op,
+                None => break,
+            };
+            if POSTFIX_OPERATORS.contains(&op) {
+                if POSTFIX_BINDING_POWER < min_binding_power {
+                    break;
+                }
+                self.advance();
+                self.count_operator(token.span)?;
+                let span = lhs.span.to(token.span);
+                lhs = Expr::new(ExprKind::Postfix { op, operand: Box::new(lhs) }, span);
+                continue;
+            }
+            let (left_power, right_power) = match binary_binding_power(op) {
+                Some(powers) => powers,
+                None => break,
+            };
+            if left_power < min_binding_power || self.next_starts_matrix_element() {
+                break;
+            }
+            self.advance();
+            if self.peek().is_none() {
+                return Err(ExpressionBuildError::HangingOperator(op.to_string(), token.span));
+            }
+            // only the right side recurses, so a flat `1 + 1 + 1` doesn't count towards the depth however long it is
+            self.count_operator(token.span)?;
+            let rhs = self.parse_expression(right_power)?;
+            lhs = make_binary(op, lhs, rhs)?;
         }
+        Ok(lhs)
+    }
 
-        match self {
-            Node::Tkn(token) => {
-                res.push_str(format!("'{}',\n", token).as_str());
+    fn parse_prefix(&mut self) -> Result<Expr, ExpressionBuildError> {
+        let token = match self.advance() {
+            Some(token) => token,
+            None => return Err(ExpressionBuildError::UnexpectedToken("end of input".to_string(), self.previous_span())),
+        };
+        match &token.kind {
+            TokenKind::Number(number) => {
+                let coefficient = Expr::new(ExprKind::Number(*number), token.span);
+                if self.implicit_multiplication && self.next_continues_coefficient() {
+                    let factor = self.parse_expression(IMPLIED_PRODUCT_BINDING_POWER)?;
+                    return make_binary("*", coefficient, factor);
+                }
+                Ok(coefficient)
+            },
+            TokenKind::Identifier(name) if self.peek().is_some_and(|next| next.as_operator() == Some("->")) => {
+                self.advance();
+                self.parse_lambda(vec![name.clone()], token)
             },
-            Node::Exp(subnodes) => {
-                res.push_str("[\n");
-                for node in subnodes {
-                    res.push_str(node.pretty_string(depth + 1).as_str());
+            TokenKind::Identifier(name) => {
+                let call_parens = self.peek().is_some_and(|next| next.is_bracket('('))
+                    && (!self.in_matrix || self.next_is_adjacent());
+                if call_parens {
+                    let opening = self.advance().expect("peeked");
+                    let args = self.parse_call_args(opening)?;
+                    Ok(Expr::new(ExprKind::Call { name: name.clone(), args, parens: true }, token.span.to(self.previous_span())))
+                } else if self.next_continues_operand() {
+                    let arg = self.parse_expression(CALL_ARGUMENT_BINDING_POWER)?;
+                    let span = token.span.to(arg.span);
+                    Ok(Expr::new(ExprKind::Call { name: name.clone(), args: vec![arg], parens: false }, span))
+                } else {
+                    Ok(Expr::new(ExprKind::Ident(name.clone()), token.span))
                 }
-                for _ in 0..depth {
-                    res.push_str("\t");
+            },
+            TokenKind::Bracket('(') if self.lambda_params().is_some() => {
+                let (params, arrow) = self.lambda_params().expect("just checked");
+                self.position = arrow + 1;
+                self.parse_lambda(params, token)
+            },
+            TokenKind::Bracket('(') => {
+                let in_matrix = std::mem::replace(&mut self.in_matrix, false);
+                let inner = self.parse_expression(0)?;
+                let closing = self.expect_closing(')', token)?;
+                self.in_matrix = in_matrix;
+                Ok(Expr::new(inner.kind, token.span.to(closing)))
+            },
+            TokenKind::Bracket('[') => self.parse_matrix(token),
+            // the else branch goes as far right as it can, so `if a then 1 else 2 + 3` ends with `2 + 3`,
+            // but inside a matrix it still stops at whitespace since there's no keyword after it
+            TokenKind::Keyword("if") => {
+                let in_matrix = std::mem::replace(&mut self.in_matrix, false);
+                let condition = self.parse_expression(0)?;
+                self.expect_keyword("then", token)?;
+                let then = self.parse_expression(0)?;
+                self.expect_keyword("else", token)?;
+                self.in_matrix = in_matrix;
+                let otherwise = self.parse_expression(0)?;
+                let span = token.span.to(otherwise.span);
+                Ok(Expr::new(ExprKind::Conditional { condition: Box::new(condition), then: Box::new(then), otherwise: Box::new(otherwise) }, span))
+            },
+            TokenKind::Operator(op) if UNARY_OPERATORS.contains(op) => {
+                if self.peek().is_none() {
+                    return Err(ExpressionBuildError::HangingOperator(op.to_string(), token.span));
                 }
-                res.push_str("]\n");
+                let operand = self.parse_expression(UNARY_BINDING_POWER)?;
+                let span = token.span.to(operand.span);
+                Ok(Expr::new(ExprKind::Unary { op, operand: Box::new(operand) }, span))
             },
+            TokenKind::Operator(op) => Err(ExpressionBuildError::HangingOperator(op.to_string(), token.span)),
+            _ => Err(ExpressionBuildError::UnexpectedToken(token.to_string(), token.span)),
         }
-        res
     }
-}
 
-impl fmt::Display for Node {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.pretty_string(0))
+    // `(x, y) -> ...` looks like a parenthesised expression until the arrow, so this scans ahead from just after
+    // the `(` for a list of names followed by `->`, giving back the names and the position of the arrow
+    fn lambda_params(&self) -> Option<(Vec<String>, usize)> {
+        let mut params = Vec::new();
+        let mut i = self.position;
+        while !self.tokens.get(i)?.is_bracket(')') {
+            params.push(self.tokens[i].as_identifier()?.clone());
+            i += 1;
+            if self.tokens.get(i)?.is_separator(',') {
+                i += 1;
+            }
+        }
+        match self.tokens.get(i + 1)?.as_operator() {
+            Some("->") => Some((params, i + 1)),
+            _ => None,
+        }
     }
-}
 
-// --------------------------------------------------------------------------------------------------------------------
+    // like the else branch of an `if`, the body goes as far right as it can, so `x -> x + 1` is all one function
+    fn parse_lambda(&mut self, params: Vec<String>, start: &Token) -> Result<Expr, ExpressionBuildError> {
+        if self.peek().is_none() {
+            return Err(ExpressionBuildError::HangingOperator("->".to_string(), self.previous_span()));
+        }
+        let body = self.parse_expression(0)?;
+        let span = start.span.to(body.span);
+        Ok(Expr::new(ExprKind::Lambda { params, body: Arc::new(body) }, span))
+    }
 
-fn parse_square_braces(nodes: &mut Vec<Node>) -> Result<(), ExpressionBuildError> {
-    let mut i = 0;
-    while i < nodes.len() {
-        if nodes[i].is_str("[") {
-            let mut j = i;
+    fn parse_call_args(&mut self, opening: &Token) -> Result<Vec<Expr>, ExpressionBuildError> {
+        let in_matrix = std::mem::replace(&mut self.in_matrix, false);
+        let mut args = Vec::new();
+        if self.peek().is_some_and(|token| token.is_bracket(')')) {
+            self.advance();
+        } else {
             loop {
-                j += 1;
-                if j >= nodes.len() {
-                    return Err(ExpressionBuildError::HangingBrace("]".to_string()));
-                }
-                if nodes[j].is_str("]") {
-                    break;
+                args.push(self.parse_expression(0)?);
+                match self.peek() {
+                    Some(token) if token.is_separator(',') => {
+                        self.advance();
+                    },
+                    _ => {
+                        self.expect_closing(')', opening)?;
+                        break;
+                    },
                 }
-            };
-            let subnodes: Vec<Node> = nodes.drain(i..=j).collect();
-            nodes.insert(i, Node::Exp(subnodes));
-        };
-        i += 1;
+            }
+        }
+        self.in_matrix = in_matrix;
+        Ok(args)
     }
-    Ok(())
-}
 
-fn parse_tree_braces(mut nodes: Vec<Node>) -> Result<Vec<Node>, ExpressionBuildError> {
-    let mut i = 0;
-    while i < nodes.len() {
-        if let Node::Tkn(token) = &nodes[i] {
-            match token.as_str() {
-                ")" => {
-                    let mut remaining_nodes = nodes.split_off(i + 1);
-                    nodes.pop(); // remove ")"
+    fn parse_matrix(&mut self, opening: &Token) -> Result<Expr, ExpressionBuildError> {
+        let in_matrix = std::mem::replace(&mut self.in_matrix, true);
+        let mut rows: Vec<Vec<Expr>> = vec![Vec::new()];
+        loop {
+            match self.peek() {
+                Some(token) if token.is_bracket(']') => {
+                    self.advance();
+                    break;
+                },
+                Some(token) if token.is_separator(',') => {
+                    self.advance();
+                },
+                Some(token) if token.is_separator(';') => {
+                    self.advance();
+                    rows.push(Vec::new());
+                },
+                Some(_) => {
+                    let element = self.parse_expression(0)?;
+                    rows.last_mut().expect("always has a row").push(element);
+                },
+                None => return Err(ExpressionBuildError::HangingBrace("]".to_string(), opening.span)),
+            }
+        }
+        self.in_matrix = in_matrix;
+        rows.retain(|row| !row.is_empty());
+        Ok(Expr::new(ExprKind::Matrix { rows }, opening.span.to(self.previous_span())))
+    }
 
-                    let expression = Node::Exp(nodes);
+    fn expect_end(&self) -> Result<(), ExpressionBuildError> {
+        match self.peek() {
+            Some(token) => Err(ExpressionBuildError::UnexpectedToken(token.to_string(), token.span)),
+            None => Ok(()),
+        }
+    }
+}
 
-                    let mut new_nodes = Vec::with_capacity(remaining_nodes.len() + 1);
+fn make_binary(op: &'static str, lhs: Expr, rhs: Expr) -> Result<Expr, ExpressionBuildError> {
+    let span = lhs.span.to(rhs.span);
+    let (target, value) = match op {
+        "=" => (lhs, rhs),
+        "=>" => (rhs, lhs),
+        _ => return Ok(Expr::new(ExprKind::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs) }, span)),
+    };
+    match target.kind {
+        ExprKind::Ident(name) => Ok(Expr::new(ExprKind::Assign { name, value: Box::new(value) }, span)),
+        ExprKind::Matrix { rows } if rows.len() == 1 => {
+            let names = rows[0].iter().map(|element| match &element.kind {
+                ExprKind::Ident(name) => Ok(name.clone()),
+                _ => Err(ExpressionBuildError::InvalidAssignment(element.span)),
+            }).collect::<Result<Vec<String>, _>>()?;
+            Ok(Expr::new(ExprKind::Destructure { names, value: Box::new(value) }, span))
+        },
+        _ => Err(ExpressionBuildError::InvalidAssignment(target.span)),
+    }
+}
 
-                    new_nodes.push(expression);
-                    new_nodes.append(&mut remaining_nodes);
-                    return Ok(new_nodes);
-                },
-                "(" => {
-                    let contained_nodes = nodes.split_off(i + 1);
-                    nodes.pop(); // remove "("
+/// Splits identifiers like `2pi` into a coefficient and a name, unless the whole thing is already a known name
+/// or is being assigned to, so `1dog = 5` still creates a variable called `1dog`.
+fn split_coefficients(token_sequence: Vec<Token>, is_known: impl Fn(&str) -> bool) -> Vec<Token> {
+    let mut split_sequence = Vec::with_capacity(token_sequence.len());
+    for (i, token) in token_sequence.iter().enumerate() {
+        let is_assignment_target = token_sequence.get(i + 1).is_some_and(|next| next.as_operator() == Some("="))
+            || (i > 0 && token_sequence[i - 1].as_operator() == Some("=>"));
 
-                    let mut parsed_nodes = parse_tree_braces(contained_nodes)?;
-                    nodes.append(&mut parsed_nodes);
-                    return Ok(nodes);
-                },
-                _ => {},
+        if let Some(name) = token.as_identifier().filter(|name| !is_assignment_target && !is_known(name)) {
+            if let Some((number, len)) = tokens::split_coefficient(name) {
+                let (start, end) = (token.span.start, token.span.end);
+                split_sequence.push(Token::new(TokenKind::Number(number), Span::new(start, start + len)));
+                split_sequence.push(Token::new(TokenKind::Identifier(name[len..].to_string()), Span::new(start + len, end)));
+                continue;
             }
         }
-        i += 1;
+        split_sequence.push(token.clone());
     }
-    Ok(nodes)
+    split_sequence
 }
 
-fn fill_missing_ans(nodes: &mut Vec<Node>) {
-    match nodes.get(0) {
-        Some(node) => {
-            if node.is_binary_operator() {
-                nodes.insert(0, Node::Tkn(Token::from("ans")));
+fn parse_function_definition(token_sequence: &[Token], environment: &Environment) -> Result<(Vec<String>, Expr), ExpressionBuildError> {
+    let mut params = Vec::new();
+    let body_start = match token_sequence.first() {
+        Some(opening) if opening.is_bracket('(') => {
+            let mut i = 1;
+            loop {
+                match token_sequence.get(i) {
+                    Some(token) if token.is_bracket(')') => break i + 1,
+                    Some(token) if token.is_separator(',') => {},
+                    Some(token) => match token.as_identifier() {
+                        Some(name) => params.push(name.clone()),
+                        None => return Err(ExpressionBuildError::InvalidFunctionDefinition(format!("'{}' is not a parameter name", token))),
+                    },
+                    None => return Err(ExpressionBuildError::HangingBrace(")".to_string(), opening.span)),
+                }
+                i += 1;
             }
         },
-        None => {}
+        Some(token) => match token.as_identifier() {
+            Some(name) => {
+                params.push(name.clone());
+                1
+            },
+            None => return Err(ExpressionBuildError::InvalidFunctionDefinition(format!("'{}' is not a parameter name", token))),
+        },
+        None => return Err(ExpressionBuildError::InvalidFunctionDefinition("emtpy function definition".to_string())),
     };
+
+    let mut body_tokens = token_sequence[body_start..].to_vec();
+    if body_tokens.is_empty() {
+        return Err(ExpressionBuildError::InvalidFunctionDefinition("missing function body".to_string()));
+    }
+    if environment.implicit_multiplication {
+        body_tokens = split_coefficients(body_tokens, |name| params.iter().any(|param| param == name) || environment.is_defined(name));
+    }
+
+    let mut parser = Parser::new(&body_tokens, environment.implicit_multiplication);
+    let body = parser.parse_expression(0)?;
+    parser.expect_end()?;
+    Ok((params, body))
 }
 
-fn parse_functions(nodes: &mut Vec<Node>) {
-    let mut i = 0;
-    while (i as isize) < (nodes.len() as isize) - 1 {
-        let current = &nodes[i].unrolled();
-        let next = &nodes[i + 1].unrolled();
-        if !current.is_operator() && !next.is_operator() {
-            let function_name = nodes.remove(i);
-            let mut function_args = vec![nodes.remove(i)];
-            parse_functions(&mut function_args);
-            nodes.insert(i, Node::Exp(vec![function_name, function_args.remove(0)]));
-        }
-        i += 1;
+fn parse_toggle(token: Option<&Token>) -> Option<bool> {
+    match token.and_then(|token| token.as_identifier()).map(|name| name.as_str()) {
+        Some("on") => Some(true),
+        Some("off") => Some(false),
+        _ => None,
     }
 }
 
-fn parse_unary(nodes: &mut Vec<Node>) {
-    let mut i = 0;
-    while i < nodes.len() {
-        match nodes[i] {
-            Node::Tkn(_) => {
-                if nodes[i].is_unary_operator() && (i == 0 || nodes[i - 1].is_operator()) {
-                    let unary_nodes: Vec<Node> = nodes.drain(i..=(i + 1)).collect();
-                    nodes.insert(i, Node::Exp(unary_nodes));
-                }
-            },
-            Node::Exp(ref mut subnodes) => {
-                parse_unary(subnodes);
-            },
-        };
-        i += 1;
+fn parse_call_depth(token: &Token) -> Option<usize> {
+    match token.kind {
+        TokenKind::Number(number) => number.as_integer()
+            .and_then(|depth| usize::try_from(depth).ok())
+            .filter(|depth| (1..=MAX_CALL_DEPTH).contains(depth)),
+        _ => None,
     }
 }
 
-// don't feel great about this code, should have a look later
-fn parse_binary(nodes: &mut Vec<Node>) -> Result<(), ExpressionBuildError> {
-    for ops in BINARY_OPERATOR_PRIORITY {
-        let mut i = 0;
-        while i < nodes.len() {
-            let len = nodes.len();
-            if len == 3 {
-                return Ok(())
-            }
-            match nodes[i] {
-                Node::Exp(ref mut subnodes) => {
-                    if len == 3 {
-                        return Ok(());
-                    }
-                    parse_binary(subnodes)?;
-                },
-                Node::Tkn(ref token) => {
-                    if ops.contains(&token.as_str()) {
-                        if i == 0 || i == len - 1 {
-                            return Err(ExpressionBuildError::HangingOperator(token.clone()));
-                        }
-                        let mut binary_nodes: Vec<Node> = nodes.drain(i-1..=i+1).collect();
-                        if let Node::Exp(ref mut subnodes) = binary_nodes[0] {
-                            parse_binary(subnodes)?
-                        }
-                        if let Node::Exp(ref mut subnodes) = binary_nodes[2] {
-                            parse_binary(subnodes)?
-                        }
-                        nodes.insert(i - 1, Node::Exp(binary_nodes));
-                    }
-                }
-            };
-            i += 1;
-        }
+fn parse_digit_cap(token: &Token) -> Option<u8> {
+    match token.kind {
+        TokenKind::Number(number) => number.as_integer().and_then(|digits| u8::try_from(digits).ok()),
+        _ => None,
     }
-    Ok(())
 }
 
 // todo: allow temporary mode updates if tokens continue past mode update
 pub fn parse_commands(token_sequence: &mut Vec<Token>, environment: &mut Environment) -> Result<String, ExpressionBuildError> {
-    match token_sequence.get(0) {
-        Some(token) => match token.as_str() {
-            "clear" => {
-                token_sequence.clear();
-                Ok(String::from("clear"))
-            },
-            "mode" => match token_sequence.get(1) {
-                Some(token) => match token.as_str() {
-                    "rad" => {
-                        environment.trig_mode = TrigMode::Rad;
-                        token_sequence.drain(..2);
-                        Ok(String::from("set mode to radians"))
-                    },
-                    "deg" => {
-                        environment.trig_mode = TrigMode::Deg;
-                        token_sequence.drain(..2);
-                        Ok(String::from("set mode to degrees"))
-                    },
-                    "digits" => match token_sequence.get(2) {
-                        Some(token) => match token.parse::<u8>() {
-                            Ok(digit) => {
-                                environment.digit_cap = digit;
-                                token_sequence.drain(..3);
-                                Ok(format!("set display digits to {digit} digits"))
-                            },
-                            Err(_) => Err(ExpressionBuildError::InvalidMode(format!("could not parse digit, got '{}'", token)))
+    let command = match token_sequence.first().and_then(|token| token.as_identifier()) {
+        Some(name) => name.clone(),
+        None => return Ok(String::new()),
+    };
+    // a command word only counts at the start of a line and in the shape of the command,
+    // so `q = 5` or `clear + 1` are still expressions, and `mode(x)` is the statistics builtin
+    let next = token_sequence.get(1);
+    let is_command = match command.as_str() {
+        "clear" | "clearvars" | "quit" | "exit" | "q" => next.is_none(),
+        "mode" | "def" => next.is_none_or(|token| token.as_identifier().is_some()),
+        _ => false,
+    };
+    if !is_command {
+        return Ok(String::new());
+    }
+    match command.as_str() {
+        "clear" => {
+            token_sequence.clear();
+            Ok(String::from("clear"))
+        },
+        "mode" => match token_sequence.get(1) {
+            Some(token) => match token.as_identifier().map(|name| name.as_str()) {
+                Some("rad") => {
+                    environment.trig_mode = TrigMode::Rad;
+                    token_sequence.drain(..2);
+                    Ok(String::from("set mode to radians"))
+                },
+                Some("deg") => {
+                    environment.trig_mode = TrigMode::Deg;
+                    token_sequence.drain(..2);
+                    Ok(String::from("set mode to degrees"))
+                },
+                Some("digits") => match token_sequence.get(2) {
+                    Some(token) => match parse_digit_cap(token) {
+                        Some(digit) => {
+                            environment.digit_cap = digit;
+                            token_sequence.drain(..3);
+                            Ok(format!("set display digits to {digit} digits"))
                         },
-                        None => Err(ExpressionBuildError::InvalidMode(format!("must provide number of digits to display")))
-                    }
-                    _ => Err(ExpressionBuildError::InvalidMode(format!("no option to change mode '{}'", token)))
-                }
-                None => {
-                    token_sequence.remove(0);
-                    let vars: String = environment.user_vars
-                        .iter()
-                        .map(|(name, value)| format!("{:?} = {:?}\n", name, value))
-                        .collect();
-                    let functions: String = environment.user_functions
-                        .iter()
-                        .map(|(name, _)| format!("function: {}()\n", name)) // could show some more info later
-                        .collect();
-                    Ok(format!("display digits: {}\ntrig mode: {:?}\nvars:\n{}\nfunctions:\n{}", environment.digit_cap, environment.trig_mode, vars, functions))
+                        None => Err(ExpressionBuildError::InvalidMode(format!("could not parse digit, got '{}'", token)))
+                    },
+                    None => Err(ExpressionBuildError::InvalidMode("must provide number of digits to display".to_string()))
                 }
-            },
-            "clearvars" => {
-                let default_env = Environment::default();
-                environment.user_vars = default_env.user_vars;
-                environment.user_functions = default_env.user_functions;
-                token_sequence.clear();
-                Ok(String::from("cleared vars"))
-            },
-            "quit" | "exit" | "q" => {
-                Ok(String::from("exit"))
-            },
-            "def" => { // first node is "def", second expects name, third params, fourth+ body
+                Some("display") => {
+                    let display_mode = match token_sequence.get(2).and_then(|token| token.as_identifier()).map(|name| name.as_str()) {
+                        Some("auto") => DisplayMode::Auto,
+                        Some("fixed") => DisplayMode::Fixed,
+                        Some("sci") => DisplayMode::Sci,
+                        Some("eng") => DisplayMode::Eng,
+                        _ => return Err(ExpressionBuildError::InvalidMode("display mode must be 'auto', 'fixed', 'sci' or 'eng'".to_string())),
+                    };
+                    environment.display_mode = display_mode;
+                    token_sequence.drain(..3);
+                    Ok(format!("set display mode to {:?}", display_mode))
+                },
+                Some("implicit") => match parse_toggle(token_sequence.get(2)) {
+                    Some(enabled) => {
+                        environment.implicit_multiplication = enabled;
+                        token_sequence.drain(..3);
+                        Ok(format!("set implicit multiplication {}", if enabled {"on"} else {"off"}))
+                    },
+                    None => Err(ExpressionBuildError::InvalidMode("implicit multiplication must be 'on' or 'off'".to_string()))
+                },
+                Some("exact") => match parse_toggle(token_sequence.get(2)) {
+                    Some(enabled) => {
+                        environment.exact_mode = enabled;
+                        token_sequence.drain(..3);
+                        Ok(format!("set exact mode {}", if enabled {"on"} else {"off"}))
+                    },
+                    None => Err(ExpressionBuildError::InvalidMode("exact mode must be 'on' or 'off'".to_string()))
+                },
+                Some("depth") => match token_sequence.get(2).and_then(parse_call_depth) {
+                    Some(depth) => {
+                        environment.max_call_depth = depth;
+                        token_sequence.drain(..3);
+                        Ok(format!("set max function call depth to {depth}"))
+                    },
+                    None => Err(ExpressionBuildError::InvalidMode(format!("call depth must be a whole number from 1 to {MAX_CALL_DEPTH}"))),
+                },
+                Some("broadcast") => match parse_toggle(token_sequence.get(2)) {
+                    Some(enabled) => {
+                        environment.broadcasting = enabled;
+                        token_sequence.drain(..3);
+                        Ok(format!("set broadcasting {}", if enabled {"on"} else {"off"}))
+                    },
+                    None => Err(ExpressionBuildError::InvalidMode("broadcasting must be 'on' or 'off'".to_string()))
+                },
+                Some("complex") => {
+                    let complex_display = match token_sequence.get(2).and_then(|token| token.as_identifier()).map(|name| name.as_str()) {
+                        Some("rect") => ComplexDisplay::Rect,
+                        Some("polar") => ComplexDisplay::Polar,
+                        _ => return Err(ExpressionBuildError::InvalidMode("complex display must be 'rect' or 'polar'".to_string())),
+                    };
+                    environment.complex_display = complex_display;
+                    token_sequence.drain(..3);
+                    Ok(format!("set complex display to {:?}", complex_display))
+                },
+                _ => Err(ExpressionBuildError::InvalidMode(format!("no option to change mode '{}'", token)))
+            }
+            None => {
                 token_sequence.remove(0);
-                let name = match token_sequence.get(0) {
-                    Some(_) => token_sequence.remove(0),
-                    None => return Err(ExpressionBuildError::InvalidFunctionDefinition("empty function definition".to_string())),
-                };
-
-                let mut function_nodes: Vec<Node> = token_sequence.iter().map(|token| Node::Tkn(token.clone())).collect();
-                function_nodes = parse_tree_braces(function_nodes)?;
-
-                if function_nodes.len() < 2 {
-                    return Err(ExpressionBuildError::InvalidFunctionDefinition("emtpy function definition".to_string()));
-                }
-
-                let params = function_nodes.remove(0);
-                let body = parse_expression_tree(function_nodes)?;
+                let vars: String = environment.user_vars
+                    .iter()
+                    .map(|(name, value)| format!("{:?} = {:?}\n", name, value))
+                    .collect();
+                let functions: String = environment.user_functions
+                    .keys()
+                    .map(|name| format!("function: {}()\n", name)) // could show some more info later
+                    .collect();
+                Ok(format!(
+                    "display mode: {:?}\ndisplay digits: {}\ntrig mode: {:?}\nimplicit multiplication: {}\nexact mode: {}\nbroadcasting: {}\nmax call depth: {}\ncomplex display: {:?}\nvars:\n{}\nfunctions:\n{}",
+                    environment.display_mode,
+                    environment.digit_cap,
+                    environment.trig_mode,
+                    environment.implicit_multiplication,
+                    environment.exact_mode,
+                    environment.broadcasting,
+                    environment.max_call_depth,
+                    environment.complex_display,
+                    vars,
+                    functions,
+                ))
+            }
+        },
+        "clearvars" => {
+            let default_env = Environment::default();
+            environment.user_vars = default_env.user_vars;
+            environment.user_functions = default_env.user_functions;
+            token_sequence.clear();
+            Ok(String::from("cleared vars"))
+        },
+        "quit" | "exit" | "q" => {
+            Ok(String::from("exit"))
+        },
+        "def" => { // first token is "def", second expects name, third params, fourth+ body
+            token_sequence.remove(0);
+            let name = match token_sequence.first().and_then(|token| token.as_identifier()) {
+                Some(name) => name.clone(),
+                None => return Err(ExpressionBuildError::InvalidFunctionDefinition("expected function name".to_string())),
+            };
+            token_sequence.remove(0);
 
-                environment.user_functions.insert(name, (params, Node::Exp(body)));
+            let (params, body) = parse_function_definition(token_sequence, environment)?;
+            let function = Function { params, body: Arc::new(body), captured: HashMap::new() };
+            environment.user_functions.insert(name, Arc::new(function));
 
-                Ok("new function".to_string())
-            },
-            _ => Ok(String::new()),
+            Ok("new function".to_string())
         },
-        None => Ok(String::new()),
+        _ => Ok(String::new()),
     }
 }
 
-fn parse_expression_tree(mut nodes: Vec<Node>) -> Result<Vec<Node>, ExpressionBuildError> {
-    parse_square_braces(&mut nodes)?;
-    nodes = parse_tree_braces(nodes)?;
-    fill_missing_ans(&mut nodes);
-    parse_functions(&mut nodes);
-    parse_unary(&mut nodes);
-    parse_binary(&mut nodes)?;
-    Ok(nodes)
+pub fn build_expression_tree(mut token_sequence: Vec<Token>, environment: &Environment) -> Result<Expr, ExpressionBuildError> {
+    if environment.implicit_multiplication {
+        token_sequence = split_coefficients(token_sequence, |name| environment.is_defined(name));
+    }
+    let mut parser = Parser::new(&token_sequence, environment.implicit_multiplication);
+
+    // a line starting with a binary operator continues from the last answer, so `+ 5` is `ans + 5` and `'` is `ans'`
+    let starts_with_binary = token_sequence.first()
+        .and_then(|token| token.as_operator())
+        .is_some_and(|op| binary_binding_power(op).is_some() || POSTFIX_OPERATORS.contains(&op));
+    let expression = match starts_with_binary {
+        true => {
+            let span = Span::new(token_sequence[0].span.start, token_sequence[0].span.start);
+            parser.parse_infix(Expr::new(ExprKind::Ident("ans".to_string()), span), 0)?
+        },
+        false => parser.parse_expression(0)?,
+    };
+    parser.expect_end()?;
+    Ok(expression)
 }
 
-pub fn build_expression_tree(token_sequence: Vec<Token>) -> Result<Node, ExpressionBuildError> {
-    let mut nodes: Vec<Node> = token_sequence.iter().map(|token| Node::Tkn(token.clone())).collect();
-    nodes = parse_expression_tree(nodes)?;
-    Ok(Node::Exp(nodes))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::MathType;
+
+    fn try_parse(text: &str, environment: &Environment) -> Result<Expr, ExpressionBuildError> {
+        build_expression_tree(tokens::generate_tokens(text).unwrap(), environment)
+    }
+
+    fn parse(text: &str, environment: &Environment) -> String {
+        try_parse(text, environment).unwrap().to_string()
+    }
+
+    fn environment_with(vars: &[&str]) -> Environment {
+        let mut environment = Environment::default();
+        for var in vars {
+            environment.user_vars.insert(var.to_string(), MathType::Number(Number::one()));
+        }
+        environment
+    }
+
+    #[test]
+    fn coefficient_before_identifier_is_product() {
+        let environment = environment_with(&["x"]);
+        assert_eq!(parse("2pi", &environment), "(2 * pi)");
+        assert_eq!(parse("2x", &environment), "(2 * x)");
+        assert_eq!(parse("2 x", &environment), "(2 * x)");
+        assert_eq!(parse("2.5x", &environment), "(2.5 * x)");
+        assert_eq!(parse("1e-3x", &environment), "(0.001 * x)");
+        assert!(matches!(try_parse("2 3", &environment), Err(ExpressionBuildError::UnexpectedToken(_, _))));
+    }
+
+    #[test]
+    fn coefficient_before_parens_is_product() {
+        let environment = environment_with(&["x"]);
+        assert_eq!(parse("3(x+1)", &environment), "(3 * (x + 1))");
+        assert_eq!(parse("3 (x+1)", &environment), "(3 * (x + 1))");
+    }
+
+    #[test]
+    fn identifier_before_parens_is_call() {
+        let environment = environment_with(&["x"]);
+        assert_eq!(parse("sin(x)", &environment), "sin(x)");
+        assert_eq!(parse("f(2)", &environment), "f(2)");
+        assert_eq!(parse("x(2)", &environment), "x(2)");
+        assert_eq!(parse("2sin(x)", &environment), "(2 * sin(x))");
+    }
+
+    #[test]
+    fn implied_product_binds_between_power_and_division() {
+        let environment = environment_with(&["x", "y"]);
+        assert_eq!(parse("2x^2", &environment), "(2 * (x ^ 2))");
+        assert_eq!(parse("1/2x", &environment), "(1 / (2 * x))");
+        assert_eq!(parse("2x + 3y", &environment), "((2 * x) + (3 * y))");
+    }
+
+    #[test]
+    fn existing_names_win_over_splitting() {
+        let environment = environment_with(&["2x"]);
+        assert_eq!(parse("2x + 1", &environment), "(2x + 1)");
+
+        let mut environment = Environment::default();
+        let mut definition = tokens::generate_tokens("def 2f(a) a").unwrap();
+        parse_commands(&mut definition, &mut environment).unwrap();
+        assert_eq!(parse("2f(3)", &environment), "2f(3)");
+    }
+
+    #[test]
+    fn assignment_targets_are_not_split() {
+        let environment = Environment::default();
+        assert_eq!(parse("1dog = 5", &environment), "(1dog = 5)");
+        assert_eq!(parse("5 => 1dog", &environment), "(1dog = 5)");
+    }
+
+    #[test]
+    fn implicit_mode_off_keeps_digit_names() {
+        let environment = Environment { implicit_multiplication: false, ..Default::default() };
+        assert_eq!(parse("2pi", &environment), "2pi");
+        assert!(try_parse("3(4)", &environment).is_err());
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        let environment = Environment::default();
+        assert_eq!(parse("1 + 2 * 3", &environment), "(1 + (2 * 3))");
+        assert_eq!(parse("1 - 2 - 3", &environment), "((1 - 2) - 3)");
+        assert_eq!(parse("2 ^ 3 ^ 2", &environment), "(2 ^ (3 ^ 2))");
+        assert_eq!(parse("(-2^2)", &environment), "(-(2 ^ 2))");
+        assert_eq!(parse("2^-1", &environment), "(2 ^ (-1))");
+        assert_eq!(parse("(-(a+b)^2)", &environment), "(-((a + b) ^ 2))");
+        assert_eq!(parse("A \\ b * 2", &environment), "((A \\ b) * 2)");
+        assert_eq!(parse("(-A' * B)", &environment), "((-(A')) * B)");
+        assert_eq!(parse("A'^2", &environment), "((A') ^ 2)");
+        assert_eq!(parse("a .* b .^ 2 ./ c", &environment), "((a .* (b .^ 2)) ./ c)");
+        assert_eq!(parse("2 .^ 3 .^ 2", &environment), "(2 .^ (3 .^ 2))");
+        assert_eq!(parse("a + 1 < b * 2", &environment), "((a + 1) < (b * 2))");
+        assert_eq!(parse("a == b || c != d && !e", &environment), "((a == b) || ((c != d) && (!e)))");
+        assert_eq!(parse("x = a >= b", &environment), "(x = (a >= b))");
+        assert_eq!(parse("sin x'", &environment), "sin((x'))");
+    }
+
+    #[test]
+    fn nested_calls() {
+        let environment = Environment::default();
+        assert_eq!(parse("f(g(x))-1", &environment), "(f(g(x)) - 1)");
+        assert_eq!(parse("add(b + 2, 4)", &environment), "add((b + 2), 4)");
+        assert_eq!(parse("double double 4", &environment), "double(double(4))");
+        assert_eq!(parse("sin x^2", &environment), "(sin(x) ^ 2)");
+        assert_eq!(parse("f()", &environment), "f()");
+    }
+
+    #[test]
+    fn matrices() {
+        let environment = Environment::default();
+        assert_eq!(parse("[1 2 3; 4 5 6]", &environment), "[1, 2, 3; 4, 5, 6]");
+        assert_eq!(parse("[1, 2; 3, 4]", &environment), "[1, 2; 3, 4]");
+        assert_eq!(parse("[(1 + 2) 3; f(4) (5)]", &environment), "[(1 + 2), 3; f(4), 5]");
+        assert_eq!(parse("[1 -2]", &environment), "[1, (-2)]");
+        assert_eq!(parse("[1 - 2]", &environment), "[(1 - 2)]");
+        assert_eq!(parse("[(1 -2)]", &environment), "[(1 - 2)]");
+        assert_eq!(parse("[2x y]", &environment), "[(2 * x), y]");
+        assert_eq!(parse("[f (x)]", &environment), "[f, x]");
+        assert_eq!(parse("[]", &environment), "[]");
+        assert_eq!(parse("[1 if a then 2 else 3 4]", &environment), "[1, (if a then 2 else 3), 4]");
+        assert_eq!(parse("[1 2]'", &environment), "([1, 2]')");
+        assert_eq!(parse("[a' b]", &environment), "[(a'), b]");
+    }
+
+    #[test]
+    fn assignment_and_implied_ans() {
+        let environment = Environment::default();
+        assert_eq!(parse("a = 1 + 2", &environment), "(a = (1 + 2))");
+        assert_eq!(parse("1 + 2 => a", &environment), "(a = (1 + 2))");
+        assert_eq!(parse("/ 2", &environment), "(ans / 2)");
+        assert_eq!(parse("-2 + 3", &environment), "((ans - 2) + 3)");
+        assert_eq!(parse("=> var", &environment), "(var = ans)");
+        assert_eq!(parse("'", &environment), "(ans')");
+        assert!(matches!(try_parse("1 + 2 = 3", &environment), Err(ExpressionBuildError::InvalidAssignment(_))));
+        assert!(matches!(try_parse("[a, 2] = b", &environment), Err(ExpressionBuildError::InvalidAssignment(_))));
+    }
+
+    #[test]
+    fn conditionals() {
+        let environment = Environment::default();
+        assert_eq!(parse("if x < 0 then -x else x", &environment), "(if (x < 0) then (-x) else x)");
+        assert_eq!(parse("if a then 1 else if b then 2 else 3", &environment), "(if a then 1 else (if b then 2 else 3))");
+        assert_eq!(parse("1 + if a then 2 else 3 * 4", &environment), "(1 + (if a then 2 else (3 * 4)))");
+        assert_eq!(parse("y = (if a then b else c) ^ 2", &environment), "(y = ((if a then b else c) ^ 2))");
+        assert!(matches!(try_parse("if a then b", &environment), Err(ExpressionBuildError::MissingKeyword("else", _))));
+        assert!(matches!(try_parse("if a else b", &environment), Err(ExpressionBuildError::UnexpectedToken(_, _))));
+    }
+
+    #[test]
+    fn lambdas() {
+        let environment = Environment::default();
+        assert_eq!(parse("f = x -> x + 1", &environment), "(f = ((x) -> (x + 1)))");
+        assert_eq!(parse("map((a, b) -> a * b, v)", &environment), "map(((a, b) -> (a * b)), v)");
+        assert_eq!(parse("(a b) -> () -> a", &environment), "((a, b) -> (() -> a))");
+        assert!(matches!(try_parse("(a, b) * 2", &environment), Err(ExpressionBuildError::UnexpectedToken(_, _))));
+    }
+
+    #[test]
+    fn malformed_input() {
+        let environment = Environment::default();
+        assert!(matches!(try_parse("(1 + 2", &environment), Err(ExpressionBuildError::HangingBrace(_, _))));
+        assert!(matches!(try_parse("[1 2", &environment), Err(ExpressionBuildError::HangingBrace(_, _))));
+        assert!(matches!(try_parse("1 +", &environment), Err(ExpressionBuildError::HangingOperator(_, _))));
+        assert!(matches!(try_parse("1 + 2)", &environment), Err(ExpressionBuildError::UnexpectedToken(_, _))));
+        assert!(matches!(try_parse("(1 + 2) 3", &environment), Err(ExpressionBuildError::UnexpectedToken(_, _))));
+    }
 }
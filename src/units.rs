@@ -0,0 +1,285 @@
+use std::fmt;
+
+use crate::executor::{ExecutionError, MathType};
+use crate::number::Number;
+
+/// Powers of the SI base units, in the order m, kg, s, A, K, mol, cd.
+pub type Dimension = [i32; 7];
+
+const LENGTH: Dimension = [1, 0, 0, 0, 0, 0, 0];
+const MASS: Dimension = [0, 1, 0, 0, 0, 0, 0];
+const TIME: Dimension = [0, 0, 1, 0, 0, 0, 0];
+const CURRENT: Dimension = [0, 0, 0, 1, 0, 0, 0];
+const TEMPERATURE: Dimension = [0, 0, 0, 0, 1, 0, 0];
+const AMOUNT: Dimension = [0, 0, 0, 0, 0, 1, 0];
+const LUMINOSITY: Dimension = [0, 0, 0, 0, 0, 0, 1];
+const FREQUENCY: Dimension = [0, 0, -1, 0, 0, 0, 0];
+const FORCE: Dimension = [1, 1, -2, 0, 0, 0, 0];
+const PRESSURE: Dimension = [-1, 1, -2, 0, 0, 0, 0];
+const ENERGY: Dimension = [2, 1, -2, 0, 0, 0, 0];
+const POWER: Dimension = [2, 1, -3, 0, 0, 0, 0];
+const CHARGE: Dimension = [0, 0, 1, 1, 0, 0, 0];
+const VOLTAGE: Dimension = [2, 1, -3, -1, 0, 0, 0];
+const RESISTANCE: Dimension = [2, 1, -3, -2, 0, 0, 0];
+const CAPACITANCE: Dimension = [-2, -1, 4, 2, 0, 0, 0];
+const MAGNETIC_FIELD: Dimension = [0, 1, -2, -1, 0, 0, 0];
+const VOLUME: Dimension = [3, 0, 0, 0, 0, 0, 0];
+const SPEED: Dimension = [1, 0, -1, 0, 0, 0, 0];
+
+// symbol, size in SI base units, dimension, whether it takes SI prefixes
+const UNITS: &[(&str, f64, Dimension, bool)] = &[
+    // grams rather than kilograms so that `kg` comes from the prefix like everything else
+    ("m", 1.0, LENGTH, true),
+    ("g", 1e-3, MASS, true),
+    ("s", 1.0, TIME, true),
+    ("A", 1.0, CURRENT, true),
+    ("K", 1.0, TEMPERATURE, true),
+    ("mol", 1.0, AMOUNT, true),
+    ("cd", 1.0, LUMINOSITY, true),
+    ("Hz", 1.0, FREQUENCY, true),
+    ("N", 1.0, FORCE, true),
+    ("Pa", 1.0, PRESSURE, true),
+    ("J", 1.0, ENERGY, true),
+    ("W", 1.0, POWER, true),
+    ("C", 1.0, CHARGE, true),
+    ("V", 1.0, VOLTAGE, true),
+    ("ohm", 1.0, RESISTANCE, true),
+    ("F", 1.0, CAPACITANCE, true),
+    ("T", 1.0, MAGNETIC_FIELD, true),
+    ("L", 1e-3, VOLUME, true),
+    ("eV", 1.602176634e-19, ENERGY, true),
+    ("cal", 4.184, ENERGY, true),
+    ("bar", 1e5, PRESSURE, true),
+    ("min", 60.0, TIME, false),
+    ("h", 3600.0, TIME, false),
+    ("day", 86400.0, TIME, false),
+    ("in", 0.0254, LENGTH, false),
+    ("ft", 0.3048, LENGTH, false),
+    ("yd", 0.9144, LENGTH, false),
+    ("mi", 1609.344, LENGTH, false),
+    ("lb", 0.45359237, MASS, false),
+    ("oz", 0.028349523125, MASS, false),
+    ("mph", 0.44704, SPEED, false),
+    ("psi", 6894.757293168361, PRESSURE, false),
+    ("atm", 101325.0, PRESSURE, false),
+];
+
+const PREFIXES: &[(&str, f64)] = &[
+    ("T", 1e12),
+    ("G", 1e9),
+    ("M", 1e6),
+    ("k", 1e3),
+    ("c", 1e-2),
+    ("m", 1e-3),
+    ("u", 1e-6),
+    ("μ", 1e-6),
+    ("n", 1e-9),
+    ("p", 1e-12),
+];
+
+// size and dimension of a single symbol like `km`, exact matches win so `min` is minutes and not milli-inches
+fn lookup(symbol: &str) -> Option<(f64, Dimension)> {
+    if let Some((_, scale, dimension, _)) = UNITS.iter().find(|unit| unit.0 == symbol) {
+        return Some((*scale, *dimension));
+    }
+    PREFIXES.iter().find_map(|(prefix, factor)| {
+        let rest = symbol.strip_prefix(prefix)?;
+        let (_, scale, dimension, _) = UNITS.iter().find(|unit| unit.0 == rest && unit.3)?;
+        Some((scale * factor, *dimension))
+    })
+}
+
+/// A product of unit symbols raised to powers, like `km/h` or `kg*m/s^2`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unit {
+    factors: Vec<(String, i32)>,
+}
+
+impl Unit {
+    pub fn parse(symbol: &str) -> Option<Unit> {
+        lookup(symbol).map(|_| Unit { factors: vec![(symbol.to_string(), 1)] })
+    }
+
+    /// Size of the unit in SI base units, so 1000 for `km`.
+    pub fn scale(&self) -> f64 {
+        self.factors.iter()
+            .map(|(symbol, power)| lookup(symbol).map(|(scale, _)| scale.powi(*power)).unwrap_or(1.0))
+            .product()
+    }
+
+    pub fn dimension(&self) -> Dimension {
+        let mut dimension: Dimension = [0; 7];
+        for (symbol, power) in &self.factors {
+            if let Some((_, base)) = lookup(symbol) {
+                for (total, base) in dimension.iter_mut().zip(base) {
+                    *total = total.saturating_add(base.saturating_mul(*power));
+                }
+            }
+        }
+        dimension
+    }
+
+    /// None if a power gets too big to store, the same goes for powi.
+    pub fn mul(&self, other: &Unit) -> Option<Unit> {
+        let mut factors = self.factors.clone();
+        for (symbol, power) in &other.factors {
+            match factors.iter_mut().find(|(existing, _)| existing == symbol) {
+                Some((_, existing)) => *existing = existing.checked_add(*power)?,
+                None => factors.push((symbol.clone(), *power)),
+            }
+        }
+        factors.retain(|(_, power)| *power != 0);
+        Some(Unit { factors })
+    }
+
+    pub fn powi(&self, exponent: i32) -> Option<Unit> {
+        let factors = self.factors.iter()
+            .map(|(symbol, power)| Some((symbol.clone(), power.checked_mul(exponent)?)))
+            .filter(|factor| factor.as_ref().is_none_or(|(_, power)| *power != 0))
+            .collect::<Option<_>>()?;
+        Some(Unit { factors })
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |factors: Vec<(&String, i32)>| factors.iter()
+            .map(|(symbol, power)| match power {
+                1 => symbol.to_string(),
+                _ => format!("{symbol}^{power}"),
+            })
+            .collect::<Vec<_>>()
+            .join("*");
+        let numerator: Vec<_> = self.factors.iter().filter(|(_, power)| *power > 0).map(|(s, p)| (s, *p)).collect();
+        let denominator: Vec<_> = self.factors.iter().filter(|(_, power)| *power < 0).map(|(s, p)| (s, -p)).collect();
+        let top = match numerator.is_empty() {
+            true => "1".to_string(),
+            false => join(numerator),
+        };
+        match denominator.len() {
+            0 => write!(f, "{top}"),
+            1 => write!(f, "{top}/{}", join(denominator)),
+            _ => write!(f, "{top}/({})", join(denominator)),
+        }
+    }
+}
+
+// a quantity whose units cancel out is just a number
+fn quantity(value: Number, unit: Unit) -> MathType {
+    match unit.dimension() == [0; 7] {
+        true => MathType::Number(scale_by(value, unit.scale())),
+        false => MathType::Quantity(value, unit),
+    }
+}
+
+// multiplying by exactly 1 would turn an exact fraction into a float for no reason
+fn scale_by(value: Number, factor: f64) -> Number {
+    match factor == 1.0 {
+        true => value,
+        false => value * Number::from(factor),
+    }
+}
+
+fn describe(unit: &Unit) -> String {
+    match unit.factors.is_empty() {
+        true => "no units".to_string(),
+        false => unit.to_string(),
+    }
+}
+
+fn convert(value: Number, from: &Unit, to: &Unit) -> Result<Number, ExecutionError> {
+    match from.dimension() == to.dimension() {
+        true => Ok(scale_by(value, from.scale() / to.scale())),
+        false => Err(ExecutionError::IncompatibleUnits(describe(from), describe(to))),
+    }
+}
+
+fn as_quantity(value: &MathType, operator: &str) -> Result<(Number, Unit), ExecutionError> {
+    match value {
+        MathType::Number(num) => Ok((*num, Unit { factors: Vec::new() })),
+        MathType::Quantity(num, unit) => Ok((*num, unit.clone())),
+        _ => Err(ExecutionError::InvalidOperation(format!("{operator} between a quantity and a {}", value.type_name()))),
+    }
+}
+
+fn power_too_large() -> ExecutionError {
+    ExecutionError::InvalidOperation("unit power is too large".to_string())
+}
+
+/// Applies an operator where at least one side has units, plain numbers are treated as unitless quantities.
+pub fn operate_quantities(lhs: &MathType, operator: &str, rhs: &MathType) -> Result<MathType, ExecutionError> {
+    let (lhs_value, lhs_unit) = as_quantity(lhs, operator)?;
+    let (rhs_value, rhs_unit) = as_quantity(rhs, operator)?;
+    let scales = matches!((lhs, rhs), (MathType::Quantity(..), MathType::Number(_)));
+    match operator {
+        "*" => Ok(quantity(lhs_value * rhs_value, lhs_unit.mul(&rhs_unit).ok_or_else(power_too_large)?)),
+        "/" => {
+            let unit = rhs_unit.powi(-1).and_then(|inverse| lhs_unit.mul(&inverse)).ok_or_else(power_too_large)?;
+            Ok(quantity(lhs_value / rhs_value, unit))
+        },
+        "\\" => operate_quantities(rhs, "/", lhs),
+        // a quantity is a single value, so going entry by entry is the same as the plain operator
+        ".*" | "./" | ".^" => operate_quantities(lhs, &operator[1..], rhs),
+        "+" => Ok(quantity(lhs_value + convert(rhs_value, &rhs_unit, &lhs_unit)?, lhs_unit)),
+        "-" => Ok(quantity(lhs_value - convert(rhs_value, &rhs_unit, &lhs_unit)?, lhs_unit)),
+        // a plain number on the right just scales, so `7 m % 2` is `1 m` and `7 m // 2` is `3 m`
+        "%" if scales => Ok(quantity(lhs_value.modulo(rhs_value), lhs_unit)),
+        "//" if scales => Ok(quantity(lhs_value.div_floor(rhs_value), lhs_unit)),
+        "%" => Ok(quantity(lhs_value.modulo(convert(rhs_value, &rhs_unit, &lhs_unit)?), lhs_unit)),
+        "//" => Ok(MathType::Number(lhs_value.div_floor(convert(rhs_value, &rhs_unit, &lhs_unit)?))),
+        "^" => match (rhs, rhs_value.as_integer().and_then(|power| i32::try_from(power).ok())) {
+            (MathType::Number(_), Some(power)) => Ok(quantity(lhs_value.pow(rhs_value), lhs_unit.powi(power).ok_or_else(power_too_large)?)),
+            _ => Err(ExecutionError::InvalidOperation("quantities can only be raised to whole number powers".to_string())),
+        },
+        "==" | "!=" | "<" | "<=" | ">" | ">=" => MathType::Number(lhs_value).operate(operator, MathType::Number(convert(rhs_value, &rhs_unit, &lhs_unit)?)),
+        // `3 ft to m` gives the left side in units of the right side
+        "to" => match rhs {
+            MathType::Quantity(..) => Ok(MathType::Quantity(convert(lhs_value, &lhs_unit, &rhs_unit)? / rhs_value, rhs_unit)),
+            _ => Err(ExecutionError::InvalidOperation("can only convert to a unit".to_string())),
+        },
+        _ => Err(ExecutionError::InvalidOperation(format!("{} {operator} {}", lhs.type_name(), rhs.type_name()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{number, unit};
+
+    #[test]
+    fn prefixes_and_symbols() {
+        assert_eq!(Unit::parse("km").unwrap().scale(), 1000.0);
+        assert_eq!(Unit::parse("kg").unwrap().dimension(), MASS);
+        assert_eq!(Unit::parse("min").unwrap().scale(), 60.0);
+        assert!(Unit::parse("kft").is_none());
+        assert!(Unit::parse("x").is_none());
+    }
+
+    #[test]
+    fn units_combine() {
+        let speed = operate_quantities(&number(5.0).operate("*", unit("m")).unwrap(), "/", &unit("s")).unwrap();
+        assert_eq!(speed.to_string(), "5 m/s");
+        let area = operate_quantities(&unit("m"), "*", &unit("m")).unwrap();
+        assert_eq!(area.to_string(), "1 m^2");
+        let ratio = operate_quantities(&unit("km"), "/", &unit("m")).unwrap();
+        assert_eq!(ratio.to_string(), "1000");
+    }
+
+    #[test]
+    fn conversion_and_mismatches() {
+        let feet = number(3.0).operate("*", unit("ft")).unwrap();
+        let meters = operate_quantities(&feet, "to", &unit("m")).unwrap();
+        assert_eq!(meters.to_string(), "0.9144 m");
+        assert!(matches!(operate_quantities(&unit("m"), "+", &unit("s")), Err(ExecutionError::IncompatibleUnits(..))));
+        assert!(matches!(operate_quantities(&unit("m"), "+", &number(1.0)), Err(ExecutionError::IncompatibleUnits(..))));
+    }
+
+    #[test]
+    fn remainders_by_plain_numbers_scale() {
+        let length = number(7.0).operate("*", unit("m")).unwrap();
+        assert_eq!(operate_quantities(&length, "%", &number(2.0)).unwrap().to_string(), "1 m");
+        assert_eq!(operate_quantities(&length, "//", &number(2.0)).unwrap().to_string(), "3 m");
+        assert_eq!(operate_quantities(&length, "//", &unit("m")).unwrap().to_string(), "7");
+        assert!(matches!(operate_quantities(&number(7.0), "%", &unit("m")), Err(ExecutionError::IncompatibleUnits(..))));
+    }
+}